@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS quotes_created_at_idx;
+DROP INDEX IF EXISTS quotes_author_idx;
+DROP INDEX IF EXISTS quotes_quote_search_idx;
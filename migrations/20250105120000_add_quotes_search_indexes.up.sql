@@ -0,0 +1,4 @@
+-- Add up migration script here
+CREATE INDEX IF NOT EXISTS quotes_quote_search_idx ON quotes USING GIN (to_tsvector('english', quote));
+CREATE INDEX IF NOT EXISTS quotes_author_idx ON quotes (lower(author));
+CREATE INDEX IF NOT EXISTS quotes_created_at_idx ON quotes (created_at);
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS quote_versions (
+    quote_id UUID NOT NULL,
+    version INT NOT NULL,
+    author TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    superseded_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    superseded_by TEXT NOT NULL,
+    PRIMARY KEY (quote_id, version)
+);
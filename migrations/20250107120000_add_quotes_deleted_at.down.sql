@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS quotes_deleted_at_idx;
+
+DELETE FROM quotes WHERE deleted_at IS NOT NULL;
+ALTER TABLE quotes DROP COLUMN IF EXISTS deleted_at;
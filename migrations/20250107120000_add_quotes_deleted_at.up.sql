@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE quotes ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS quotes_deleted_at_idx ON quotes (deleted_at) WHERE deleted_at IS NOT NULL;
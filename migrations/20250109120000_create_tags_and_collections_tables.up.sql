@@ -0,0 +1,29 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS tags (
+    name TEXT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS quote_tags (
+    quote_id UUID NOT NULL REFERENCES quotes (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL REFERENCES tags (name) ON DELETE CASCADE,
+    tagged_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (quote_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS quote_tags_tag_idx ON quote_tags (tag);
+
+CREATE TABLE IF NOT EXISTS collections (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS collection_quotes (
+    collection_id UUID NOT NULL REFERENCES collections (id) ON DELETE CASCADE,
+    quote_id UUID NOT NULL REFERENCES quotes (id) ON DELETE CASCADE,
+    position INT NOT NULL,
+    PRIMARY KEY (collection_id, quote_id)
+);
+
+CREATE INDEX IF NOT EXISTS collection_quotes_position_idx ON collection_quotes (collection_id, position);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS quotes_notify_change ON quotes;
+DROP FUNCTION IF EXISTS notify_quote_change();
@@ -0,0 +1,40 @@
+-- Add up migration script here
+-- Announces quote changes on the quote_changes channel as
+-- {"event": "created" | "updated" | "deleted", "id": ...}. Only the id is
+-- sent since payloads are limited to 8000 bytes, listeners load the quote.
+CREATE OR REPLACE FUNCTION notify_quote_change() RETURNS TRIGGER AS $$
+DECLARE
+    event TEXT;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        event := 'created';
+    ELSIF TG_OP = 'DELETE' THEN
+        -- Purged quotes were already announced when they were trashed
+        IF OLD.deleted_at IS NOT NULL THEN
+            RETURN NULL;
+        END IF;
+        event := 'deleted';
+    ELSIF OLD.deleted_at IS NULL AND NEW.deleted_at IS NOT NULL THEN
+        event := 'deleted';
+    ELSIF OLD.deleted_at IS NOT NULL AND NEW.deleted_at IS NULL THEN
+        event := 'created';
+    ELSIF NEW.deleted_at IS NULL THEN
+        event := 'updated';
+    ELSE
+        RETURN NULL;
+    END IF;
+
+    PERFORM pg_notify('quote_changes', json_build_object(
+        'event', event,
+        'id', CASE WHEN TG_OP = 'DELETE' THEN OLD.id ELSE NEW.id END
+    )::TEXT);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS quotes_notify_change ON quotes;
+
+CREATE TRIGGER quotes_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON quotes
+    FOR EACH ROW EXECUTE FUNCTION notify_quote_change();
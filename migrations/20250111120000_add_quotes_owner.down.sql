@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE quote_versions DROP COLUMN IF EXISTS changed_by;
+ALTER TABLE quotes DROP COLUMN IF EXISTS changed_by;
+ALTER TABLE quotes DROP COLUMN IF EXISTS owner;
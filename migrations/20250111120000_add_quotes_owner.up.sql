@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- Quotes created before owners existed have none and can only be changed by
+-- admins. changed_by is whoever made the current version, or trashed or
+-- restored the quote since.
+ALTER TABLE quotes ADD COLUMN IF NOT EXISTS owner TEXT;
+ALTER TABLE quotes ADD COLUMN IF NOT EXISTS changed_by TEXT;
+ALTER TABLE quote_versions ADD COLUMN IF NOT EXISTS changed_by TEXT;
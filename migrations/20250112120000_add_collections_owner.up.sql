@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Collections created before owners existed have none and can only be
+-- changed by admins.
+ALTER TABLE collections ADD COLUMN IF NOT EXISTS owner TEXT;
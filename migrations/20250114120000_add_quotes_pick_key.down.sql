@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS quotes_author_pick_key_idx;
+DROP INDEX IF EXISTS quotes_pick_key_idx;
+ALTER TABLE quotes DROP COLUMN IF EXISTS pick_key;
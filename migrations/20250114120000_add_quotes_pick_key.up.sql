@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- A random key per quote, so /19/random and /19/daily can pick a quote with
+-- an index lookup instead of counting every matching quote.
+ALTER TABLE quotes ADD COLUMN IF NOT EXISTS pick_key DOUBLE PRECISION NOT NULL DEFAULT random();
+
+CREATE INDEX IF NOT EXISTS quotes_pick_key_idx ON quotes (pick_key) WHERE deleted_at IS NULL;
+CREATE INDEX IF NOT EXISTS quotes_author_pick_key_idx ON quotes (lower(author), pick_key) WHERE deleted_at IS NULL;
@@ -0,0 +1,45 @@
+-- Add up migration script here
+-- Sends the changed quote along with its id, so listeners needn't load it and
+-- quotes deleted outright are still announced with their content. Payloads
+-- are limited to 8000 bytes, quotes too long to fit are left out.
+CREATE OR REPLACE FUNCTION notify_quote_change() RETURNS TRIGGER AS $$
+DECLARE
+    event TEXT;
+    changed quotes;
+    payload TEXT;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        event := 'created';
+    ELSIF TG_OP = 'DELETE' THEN
+        -- Purged quotes were already announced when they were trashed
+        IF OLD.deleted_at IS NOT NULL THEN
+            RETURN NULL;
+        END IF;
+        event := 'deleted';
+    ELSIF OLD.deleted_at IS NULL AND NEW.deleted_at IS NOT NULL THEN
+        event := 'deleted';
+    ELSIF OLD.deleted_at IS NOT NULL AND NEW.deleted_at IS NULL THEN
+        event := 'created';
+    ELSIF NEW.deleted_at IS NULL THEN
+        event := 'updated';
+    ELSE
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP = 'DELETE' THEN
+        changed := OLD;
+    ELSE
+        changed := NEW;
+    END IF;
+
+    payload := json_build_object('event', event, 'id', changed.id, 'quote', row_to_json(changed))::TEXT;
+
+    IF octet_length(payload) >= 8000 THEN
+        payload := json_build_object('event', event, 'id', changed.id)::TEXT;
+    END IF;
+
+    PERFORM pg_notify('quote_changes', payload);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- SQLite counterparts of the Postgres migrations share their versions, so
+-- both databases report and roll back the same steps. The Postgres-only ones,
+-- the change trigger and the pick key, have no counterpart here. Ids are
+-- stored as 16 byte blobs and times as RFC 3339 text, bound by the
+-- application rather than defaulted, as CURRENT_TIMESTAMP isn't RFC 3339 and
+-- wouldn't sort with them.
+CREATE TABLE IF NOT EXISTS quotes (
+    id BLOB PRIMARY KEY,
+    author TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    version INTEGER NOT NULL DEFAULT 1
+);
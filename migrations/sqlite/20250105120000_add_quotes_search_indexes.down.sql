@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS quotes_created_at_idx;
+DROP INDEX IF EXISTS quotes_author_idx;
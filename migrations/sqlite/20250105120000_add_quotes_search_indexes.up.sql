@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- SQLite has no counterpart of the Postgres full text index, quotes are
+-- matched by scanning them.
+CREATE INDEX IF NOT EXISTS quotes_author_idx ON quotes (lower(author));
+CREATE INDEX IF NOT EXISTS quotes_created_at_idx ON quotes (created_at);
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS quote_versions (
+    quote_id BLOB NOT NULL,
+    version INTEGER NOT NULL,
+    author TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    superseded_at TEXT NOT NULL,
+    superseded_by TEXT NOT NULL,
+    PRIMARY KEY (quote_id, version)
+);
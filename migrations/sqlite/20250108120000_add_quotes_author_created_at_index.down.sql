@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS quotes_author_created_at_idx;
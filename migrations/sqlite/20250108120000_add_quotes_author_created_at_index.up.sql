@@ -0,0 +1,2 @@
+-- Add up migration script here
+CREATE INDEX IF NOT EXISTS quotes_author_created_at_idx ON quotes (lower(author), created_at) WHERE deleted_at IS NULL;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS collection_quotes;
+DROP TABLE IF EXISTS collections;
+DROP TABLE IF EXISTS quote_tags;
+DROP TABLE IF EXISTS tags;
@@ -0,0 +1,29 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS tags (
+    name TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS quote_tags (
+    quote_id BLOB NOT NULL REFERENCES quotes (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL REFERENCES tags (name) ON DELETE CASCADE,
+    tagged_at TEXT NOT NULL,
+    PRIMARY KEY (quote_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS quote_tags_tag_idx ON quote_tags (tag);
+
+CREATE TABLE IF NOT EXISTS collections (
+    id BLOB PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS collection_quotes (
+    collection_id BLOB NOT NULL REFERENCES collections (id) ON DELETE CASCADE,
+    quote_id BLOB NOT NULL REFERENCES quotes (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (collection_id, quote_id)
+);
+
+CREATE INDEX IF NOT EXISTS collection_quotes_position_idx ON collection_quotes (collection_id, position);
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE quotes ADD COLUMN owner TEXT;
+ALTER TABLE quotes ADD COLUMN changed_by TEXT;
+ALTER TABLE quote_versions ADD COLUMN changed_by TEXT;
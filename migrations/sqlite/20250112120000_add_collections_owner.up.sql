@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE collections ADD COLUMN owner TEXT;
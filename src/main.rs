@@ -1,15 +1,21 @@
 use std::sync::Arc;
 use rand::rngs::StdRng;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 use tokio::time::{interval, Duration};
 use rand::SeedableRng;
-use axum::{routing::{get, post, delete, put}, Router};
-use sqlx::PgPool;
-use tower_http::services::ServeDir;
+use axum::{middleware, routing::{get, post, delete, put}, Router};
+use shuttle_runtime::{CustomError, SecretStore};
+use sqlx::{sqlite::SqliteConnectOptions, PgPool, SqlitePool};
+use tower_http::services::{ServeDir, ServeFile};
 
 pub mod routes;
 use routes::{
-    day_five::process_manifest, day_minus_one::{
+    day_five::{
+        process_manifest,
+        dependency_graph::analyze_dependencies,
+        policy::{ManifestPolicy, MANIFEST_POLICY_PATH}
+    },
+    day_minus_one::{
         bonus_minus_one, 
         hello_bird
     }, 
@@ -29,20 +35,62 @@ use routes::{
     },
     day_sixteen::{
         wrap,
-        unwrap
+        unwrap,
+        list_keys,
+        add_key,
+        retire_key,
+        jwks,
+        decode_external,
+        revoke,
+        logout,
+        inspect_token,
+        keyring::{Keyring, GIFT_TOKENS_CONFIG_PATH},
+        revocation_controller::RevocationController
     },
     day_nineteen::{
-        quote_controller::QuoteController,
+        quote_repository::QuoteRepository,
+        memory_quote_repository::MemoryQuoteRepository,
+        pg_quote_repository::PgQuoteRepository,
+        sqlite_quote_repository::SqliteQuoteRepository,
+        events::{events, relay_quote_changes, QuoteEvent, QUOTE_EVENTS_CAPACITY},
+        auth::issue_token,
+        ui,
+        config::{QuotesConfig, StoreBackend, QUOTES_CONFIG_PATH},
+        ResetConfirmation,
         draft,
         cite,
         remove,
         undo,
-        reset
+        reset,
+        search,
+        history,
+        revert,
+        restore,
+        trash,
+        export,
+        import,
+        random,
+        daily,
+        tags::{list_tags, tag, untag, tagged},
+        collections::{
+            list_collections,
+            create_collection,
+            get_collection,
+            update_collection,
+            delete_collection
+        }
     },
     day_twenty_three::{
         light_star,
         change_color,
         change_ornament
+    },
+    schema::{
+        explain,
+        list_migrations,
+        rollback_migrations,
+        require_current_schema,
+        schema_controller::SchemaController
     }
 };
 
@@ -50,28 +98,162 @@ pub struct AppState {
     pub bucket: Mutex<Bucket>,
     pub board: Mutex<Board>,
     pub rng: Mutex<StdRng>,
-    pub secret: Mutex<String>,
-    pub quote_controller: QuoteController,
+    pub gift_keyring: Mutex<Keyring>,
+    pub admin_token: Option<String>,
+    pub quote_repository: Arc<dyn QuoteRepository>,
+    pub quotes_config: QuotesConfig,
+    pub reset_confirmation: Mutex<Option<ResetConfirmation>>,
+    /// `None` unless quotes are kept in Postgres, which announces changes.
+    pub quote_events: Option<broadcast::Sender<QuoteEvent>>,
+    pub revocation_controller: RevocationController,
+    /// `None` when nothing is kept in a database.
+    pub schema_controller: Option<SchemaController>,
+    pub manifest_policy: ManifestPolicy,
+}
+
+#[cfg(feature = "shared-db")]
+#[shuttle_runtime::main]
+async fn main(
+    #[shuttle_shared_db::Postgres] database_url: String,
+    #[shuttle_runtime::Secrets] secrets: SecretStore,
+) -> shuttle_axum::ShuttleAxum {
+    serve(Some(database_url), secrets).await
 }
 
+/// Shuttle provisions no database, so the `postgres` quote store connects to
+/// the one in the DATABASE_URL secret.
+#[cfg(not(feature = "shared-db"))]
 #[shuttle_runtime::main]
 async fn main(
-    #[shuttle_shared_db::Postgres] pool: PgPool,
+    #[shuttle_runtime::Secrets] secrets: SecretStore,
+) -> shuttle_axum::ShuttleAxum {
+    serve(secrets.get("DATABASE_URL"), secrets).await
+}
+
+async fn serve(
+    database_url: Option<String>,
+    secrets: SecretStore
 ) -> shuttle_axum::ShuttleAxum {
 
-    sqlx::migrate!()
-        .run(&pool)
-        .await
-        .expect("Failed to run migrations");
+    let quotes_config = QuotesConfig::load(QUOTES_CONFIG_PATH)
+        .map_err(|e| CustomError::msg(format!("Failed to load quotes config: {}", e)))?;
+
+    // Usually a secret named in gift_tokens.toml missing from Secrets.toml
+    let gift_keyring = Keyring::load(GIFT_TOKENS_CONFIG_PATH, |name| secrets.get(name))
+        .map_err(|e| CustomError::msg(format!("Failed to load gift token keys: {}", e)))?;
+
+    let manifest_policy = ManifestPolicy::load(MANIFEST_POLICY_PATH)
+        .map_err(|e| CustomError::msg(format!("Failed to load manifest policy: {}", e)))?;
+
+    // Postgres is only connected to when something is kept there
+    let (quote_repository, revocation_controller, schema_controller, quote_changes_pool): (Arc<dyn QuoteRepository>, _, _, _) = match quotes_config.store.backend {
+        StoreBackend::Memory => {
+            println!("Keeping quotes and gift token revocations in memory, they won't survive a restart");
+
+            (Arc::new(MemoryQuoteRepository::default()), RevocationController::in_memory(), None, None)
+        },
+        StoreBackend::Sqlite => {
+            let path = &quotes_config.store.sqlite_path;
+            println!("Keeping quotes and gift token revocations in {}", path.display());
+
+            let pool = SqlitePool::connect_with(
+                SqliteConnectOptions::new()
+                    .filename(path)
+                    .create_if_missing(true)
+            )
+            .await
+            .map_err(|e| CustomError::msg(format!("Failed to open SQLite database {}: {}", path.display(), e)))?;
+
+            let schema_controller = SchemaController::sqlite(pool.clone());
+            schema_controller.migrate()
+                .await
+                .map_err(|e| CustomError::msg(format!("SQLite schema: {}", explain(&e))))?;
+
+            (Arc::new(SqliteQuoteRepository::build(pool.clone())), RevocationController::sqlite(pool), Some(schema_controller), None)
+        },
+        StoreBackend::Postgres => {
+            let database_url = database_url
+                .ok_or_else(|| CustomError::msg("The postgres quote store needs the shared-db feature or a DATABASE_URL secret"))?;
+
+            let pool = PgPool::connect(&database_url)
+                .await
+                .map_err(|e| CustomError::msg(format!("Failed to connect to Postgres: {}", e)))?;
+
+            let schema_controller = SchemaController::build(pool.clone());
+            schema_controller.migrate()
+                .await
+                .map_err(|e| CustomError::msg(format!("Postgres schema: {}", explain(&e))))?;
+
+            (
+                Arc::new(PgQuoteRepository::build(pool.clone())),
+                RevocationController::build(pool.clone()),
+                Some(schema_controller),
+                Some(pool)
+            )
+        }
+    };
+
+    let quote_events = quote_changes_pool.as_ref()
+        .map(|_| broadcast::channel(QUOTE_EVENTS_CAPACITY).0);
+
+    if let (Some(pool), Some(sender)) = (quote_changes_pool, quote_events.clone()) {
+        tokio::spawn(relay_quote_changes(pool, quote_repository.clone(), sender));
+    }
 
     let app_state = Arc::new(AppState {
         bucket: Mutex::new(Bucket::init()),
         board: Mutex::new(Board::new()),
         rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(2024)),
-        secret: Mutex::new(String::from("ULTRA_SECRET!!")),
-        quote_controller: QuoteController::build(pool)
+        gift_keyring: Mutex::new(gift_keyring),
+        admin_token: secrets.get("ADMIN_TOKEN"),
+        quote_repository,
+        quotes_config,
+        reset_confirmation: Mutex::new(None),
+        quote_events,
+        revocation_controller,
+        schema_controller,
+        manifest_policy
     });
     
+    // Everything kept in the database, refused once its migrations are rolled
+    // back
+    let store_router = Router::new()
+        .route("/16/wrap", post(wrap))
+        .route("/16/unwrap", get(unwrap))
+        .route("/16/keys", get(list_keys).post(add_key))
+        .route("/16/keys/:kid", delete(retire_key))
+        .route("/16/.well-known/jwks.json", get(jwks))
+        .route("/16/decode", post(decode_external))
+        .route("/16/revoke", post(revoke))
+        .route("/16/logout", post(logout))
+        .route("/16/inspect", post(inspect_token))
+        .route("/19/reset", post(reset))
+        .route("/19/cite/:id", get(cite))
+        .route("/19/remove/:id", delete(remove))
+        .route("/19/undo/:id", put(undo))
+        .route("/19/draft", post(draft))
+        .route("/19/search", get(search))
+        .route("/19/history/:id", get(history))
+        .route("/19/revert/:id/:version", put(revert))
+        .route("/19/restore/:id", post(restore))
+        .route("/19/trash", get(trash))
+        .route("/19/export", get(export))
+        .route("/19/import", post(import))
+        .route("/19/random", get(random))
+        .route("/19/daily", get(daily))
+        .route("/19/tags/:id", get(list_tags))
+        .route("/19/tags/:id/:tag", put(tag).delete(untag))
+        .route("/19/tagged/:tag", get(tagged))
+        .route("/19/collections", get(list_collections).post(create_collection))
+        .route("/19/collections/:id", get(get_collection).patch(update_collection).delete(delete_collection))
+        .route("/19/events", get(events))
+        .route("/19/tokens", post(issue_token))
+        .route_service("/19/ui", ServeFile::new("assets/19.html"))
+        .route("/19/ui/quotes", get(ui::list).post(ui::create))
+        .route("/19/ui/quotes/:id", get(ui::show).put(ui::update).delete(ui::delete))
+        .route("/19/ui/quotes/:id/edit", get(ui::edit))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), require_current_schema));
+
     let router = Router::new()
         .route("/", get(hello_bird))
         .route("/-1/seek", get(bonus_minus_one))
@@ -80,25 +262,27 @@ async fn main(
         .route("/2/v6/dest", get(decrypt_destination_v6))
         .route("/2/v6/key", get(decrypt_key_v6))
         .route("/5/manifest", post(process_manifest))
+        .route("/5/dependencies", post(analyze_dependencies))
         .route("/9/milk", post(leaky_bucket))
         .route("/9/refill", post(refill_bucket))
         .route("/12/board", get(create_board))
         .route("/12/place/:team/:column", post(place_item))
         .route("/12/reset", post(reset_board))
         .route("/12/random-board", get(generate_random_board))
-        .route("/16/wrap", post(wrap))
-        .route("/16/unwrap", get(unwrap))
-        .route("/19/reset", post(reset))
-        .route("/19/cite/:id", get(cite))
-        .route("/19/remove/:id", delete(remove))
-        .route("/19/undo/:id", put(undo))
-        .route("/19/draft", post(draft))
+        .merge(store_router)
+        .route("/admin/migrations", get(list_migrations))
+        .route("/admin/migrations/rollback", post(rollback_migrations))
         .route("/23/star", get(light_star))
         .route("/23/present/:color", get(change_color))
         .route("/23/ornament/:state/:n", get(change_ornament))
         .nest_service("/assets", ServeDir::new("assets"))
         .with_state(app_state.clone());
 
+    let revocation_controller = app_state.revocation_controller.clone();
+    let quote_repository = app_state.quote_repository.clone();
+    let trash_retention = app_state.quotes_config.trash.retention;
+    let purge_interval = app_state.quotes_config.trash.purge_interval;
+
     tokio::spawn(async move {
         let mut interval = interval(Duration::from_secs(1));
         loop {
@@ -107,5 +291,29 @@ async fn main(
         }
     });
 
+    tokio::spawn(async move {
+        let mut interval = interval(Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match revocation_controller.prune_expired().await {
+                Ok(pruned) => println!("Pruned {} expired gift token revocations", pruned),
+                Err(e) => println!("Error pruning gift token revocations: {}", e)
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut interval = interval(Duration::from_secs(purge_interval));
+        loop {
+            interval.tick().await;
+            // QuotesConfig::load keeps the retention within a century
+            let deleted_before = chrono::Utc::now() - chrono::Duration::seconds(trash_retention as i64);
+            match quote_repository.purge_deleted(&deleted_before).await {
+                Ok(purged) => println!("Purged {} trashed quotes", purged),
+                Err(e) => println!("Error purging trashed quotes: {}", e)
+            }
+        }
+    });
+
     Ok(router.into())
 }
@@ -0,0 +1,29 @@
+use axum::http::{header::AUTHORIZATION, HeaderMap, StatusCode};
+
+/// Checks the `Authorization: Bearer <token>` header against the configured
+/// admin token. Admin endpoints are disabled entirely when no token is set.
+pub fn require_admin(
+    headers: &HeaderMap,
+    admin_token: Option<&str>
+) -> Result<(), StatusCode> {
+    let admin_token = admin_token.ok_or(StatusCode::FORBIDDEN)?;
+
+    let provided = headers.get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if !constant_time_eq(provided.as_bytes(), admin_token.as_bytes()) {
+        return Err(StatusCode::UNAUTHORIZED)
+    }
+
+    Ok(())
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false
+    }
+
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
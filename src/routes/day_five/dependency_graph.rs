@@ -0,0 +1,494 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use axum::{extract::{Multipart, Query}, http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, StatusCode}, response::{IntoResponse, Response}, Json};
+use cargo_manifest::{DepsSet, Manifest};
+use serde::{Deserialize, Serialize};
+
+use super::{diagnostics::ManifestParseError, format::ManifestFormat, ManifestError};
+
+#[derive(Deserialize, Debug)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockedPackage>
+}
+
+#[derive(Deserialize, Debug)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    #[serde(default)]
+    dependencies: Vec<String>
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PackageId {
+    pub name: String,
+    pub version: String
+}
+
+impl PackageId {
+    fn label(&self) -> String {
+        format!("{} {}", self.name, self.version)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DirectDependency {
+    pub name: String,
+    pub package: String,
+    pub kind: &'static str,
+    /// The `cfg(...)` or triple of a `[target.*.dependencies]` table.
+    pub target: Option<String>,
+    pub requirement: String,
+    pub resolved_version: Option<String>,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>
+}
+
+#[derive(Serialize, Debug)]
+pub struct DuplicateCrate {
+    pub name: String,
+    pub versions: Vec<String>
+}
+
+#[derive(Serialize, Debug)]
+pub struct DependencyEdge {
+    pub from: PackageId,
+    pub to: PackageId
+}
+
+#[derive(Serialize, Debug)]
+pub struct DependencyGraph {
+    pub root: Option<PackageId>,
+    pub resolved: bool,
+    pub direct: Vec<DirectDependency>,
+    pub transitive: Vec<PackageId>,
+    pub duplicates: Vec<DuplicateCrate>,
+    pub edges: Vec<DependencyEdge>
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    #[default]
+    Json,
+    Dot
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GraphQuery {
+    #[serde(default)]
+    pub format: GraphFormat
+}
+
+/// Accepts a multipart upload with a `manifest` (Cargo.toml) field and an
+/// optional `lockfile` (Cargo.lock) field. Fields may also be identified by
+/// their file name.
+pub async fn analyze_dependencies(
+    Query(query): Query<GraphQuery>,
+    mut multipart: Multipart
+) -> Result<Response, ManifestError> {
+    let mut manifest_source: Option<String> = None;
+    let mut lockfile_source: Option<String> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(upload_error)? {
+        let is_manifest = field.name() == Some("manifest") || field.file_name() == Some("Cargo.toml");
+        let is_lockfile = field.name() == Some("lockfile") || field.file_name() == Some("Cargo.lock");
+
+        if !is_manifest && !is_lockfile {
+            continue
+        }
+
+        let text = field.text().await.map_err(upload_error)?;
+
+        if is_manifest {
+            manifest_source = Some(text);
+        } else {
+            lockfile_source = Some(text);
+        }
+    }
+
+    let manifest_source = manifest_source.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let manifest: Manifest = toml::from_str(&manifest_source)
+        .map_err(|e| ManifestParseError::from_toml(&e, &manifest_source))?;
+
+    let lockfile: Option<Lockfile> = match &lockfile_source {
+        Some(source) => Some(
+            toml::from_str(source).map_err(|e| ManifestParseError::from_toml(&e, source))?
+        ),
+        None => None
+    };
+
+    let graph = DependencyGraph::build(&manifest, lockfile.as_ref())?;
+
+    match query.format {
+        GraphFormat::Json => Ok(Json(graph).into_response()),
+        GraphFormat::Dot => {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/vnd.graphviz"));
+            Ok((headers, graph.to_dot()).into_response())
+        }
+    }
+}
+
+fn upload_error(error: axum::extract::multipart::MultipartError) -> ManifestParseError {
+    ManifestParseError::without_location(
+        ManifestFormat::Toml,
+        format!("Error reading multipart field: {}", error)
+    )
+}
+
+impl DependencyGraph {
+    /// Fails when the lockfile references a package it doesn't list.
+    fn build(manifest: &Manifest, lockfile: Option<&Lockfile>) -> Result<Self, ManifestParseError> {
+        let mut direct = Vec::new();
+        for (kind, deps) in [
+            ("normal", &manifest.dependencies),
+            ("dev", &manifest.dev_dependencies),
+            ("build", &manifest.build_dependencies),
+        ] {
+            if let Some(deps) = deps {
+                direct.extend(direct_dependencies(kind, None, deps));
+            }
+        }
+
+        for (target, deps) in manifest.target.iter().flatten() {
+            for (kind, deps) in [
+                ("normal", &deps.dependencies),
+                ("dev", &deps.dev_dependencies),
+                ("build", &deps.build_dependencies),
+            ] {
+                direct.extend(direct_dependencies(kind, Some(target), deps));
+            }
+        }
+
+        let root_name = manifest.package.as_ref().map(|package| package.name.clone());
+
+        let locked_root = match (lockfile, &root_name) {
+            (Some(lockfile), Some(root_name)) => lockfile.package.iter()
+                .find(|package| &package.name == root_name),
+            _ => None
+        };
+
+        let (lockfile, locked_root) = match (lockfile, locked_root) {
+            (Some(lockfile), Some(locked_root)) => (lockfile, locked_root),
+            _ => {
+                let root = root_name.map(|name| PackageId {
+                    name,
+                    version: manifest.package.as_ref()
+                        .and_then(|package| package.version.clone())
+                        .and_then(|version| version.as_local())
+                        .unwrap_or_default()
+                });
+
+                let edges = match &root {
+                    Some(root) => direct.iter().map(|dependency| DependencyEdge {
+                        from: root.clone(),
+                        to: PackageId {
+                            name: dependency.package.clone(),
+                            version: dependency.requirement.clone()
+                        }
+                    }).collect(),
+                    None => Vec::new()
+                };
+
+                return Ok(Self {
+                    root,
+                    resolved: false,
+                    direct,
+                    transitive: Vec::new(),
+                    duplicates: Vec::new(),
+                    edges
+                })
+            }
+        };
+
+        let root = PackageId {
+            name: locked_root.name.clone(),
+            version: locked_root.version.clone()
+        };
+
+        let mut edges = Vec::new();
+        let mut visited: BTreeSet<PackageId> = BTreeSet::from([root.clone()]);
+        let mut queue: VecDeque<&LockedPackage> = VecDeque::from([locked_root]);
+
+        while let Some(package) = queue.pop_front() {
+            let from = PackageId {
+                name: package.name.clone(),
+                version: package.version.clone()
+            };
+
+            for reference in package.dependencies.iter() {
+                let dependency = resolve_reference(lockfile, reference).ok_or_else(|| {
+                    ManifestParseError::without_location(
+                        ManifestFormat::Toml,
+                        format!("Cargo.lock dependency of {} isn't listed: {}", from.label(), reference)
+                    )
+                })?;
+
+                let to = PackageId {
+                    name: dependency.name.clone(),
+                    version: dependency.version.clone()
+                };
+
+                edges.push(DependencyEdge { from: from.clone(), to: to.clone() });
+
+                if visited.insert(to) {
+                    queue.push_back(dependency);
+                }
+            }
+        }
+
+        let direct_ids: BTreeSet<PackageId> = edges.iter()
+            .filter(|edge| edge.from == root)
+            .map(|edge| edge.to.clone())
+            .collect();
+
+        for dependency in direct.iter_mut() {
+            dependency.resolved_version = direct_ids.iter()
+                .find(|id| id.name == dependency.package)
+                .map(|id| id.version.clone());
+        }
+
+        let transitive = visited.iter()
+            .filter(|id| **id != root && !direct_ids.contains(id))
+            .cloned()
+            .collect();
+
+        let mut versions_by_name: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for id in visited.iter() {
+            versions_by_name.entry(&id.name).or_default().push(id.version.clone());
+        }
+
+        let duplicates = versions_by_name.into_iter()
+            .filter(|(_, versions)| versions.len() > 1)
+            .map(|(name, versions)| DuplicateCrate { name: name.to_string(), versions })
+            .collect();
+
+        Ok(Self {
+            root: Some(root),
+            resolved: true,
+            direct,
+            transitive,
+            duplicates,
+            edges
+        })
+    }
+
+    pub fn to_dot(&self) -> String {
+        let duplicated: BTreeSet<&str> = self.duplicates.iter()
+            .map(|duplicate| duplicate.name.as_str())
+            .collect();
+
+        let features: HashMap<&str, String> = self.direct.iter()
+            .filter(|dependency| !dependency.features.is_empty())
+            .map(|dependency| (dependency.package.as_str(), dependency.features.join(", ")))
+            .collect();
+
+        let mut lines = vec!["digraph dependencies {".to_string()];
+
+        if let Some(root) = &self.root {
+            lines.push(format!("    {} [shape=box, style=bold];", dot_id(&root.label())));
+        }
+
+        let mut nodes: BTreeSet<&PackageId> = BTreeSet::new();
+        for edge in self.edges.iter() {
+            nodes.insert(&edge.to);
+        }
+
+        for node in nodes {
+            if duplicated.contains(node.name.as_str()) {
+                lines.push(format!("    {} [color=red];", dot_id(&node.label())));
+            }
+        }
+
+        for edge in self.edges.iter() {
+            let from_root = self.root.as_ref() == Some(&edge.from);
+            match features.get(edge.to.name.as_str()).filter(|_| from_root) {
+                Some(features) => lines.push(format!(
+                    "    {} -> {} [label={}];",
+                    dot_id(&edge.from.label()),
+                    dot_id(&edge.to.label()),
+                    dot_id(features)
+                )),
+                None => lines.push(format!(
+                    "    {} -> {};",
+                    dot_id(&edge.from.label()),
+                    dot_id(&edge.to.label())
+                ))
+            }
+        }
+
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+}
+
+fn direct_dependencies(
+    kind: &'static str,
+    target: Option<&String>,
+    deps: &DepsSet
+) -> Vec<DirectDependency> {
+    deps.iter().map(|(name, dependency)| {
+        let detail = dependency.detail();
+
+        DirectDependency {
+            name: name.clone(),
+            package: detail
+                .and_then(|detail| detail.package.clone())
+                .unwrap_or_else(|| name.clone()),
+            kind,
+            target: target.cloned(),
+            requirement: dependency.req().to_string(),
+            resolved_version: None,
+            optional: detail.and_then(|detail| detail.optional).unwrap_or(false),
+            default_features: detail.and_then(|detail| detail.default_features).unwrap_or(true),
+            features: detail
+                .and_then(|detail| detail.features.clone())
+                .unwrap_or_default()
+        }
+    }).collect()
+}
+
+/// Cargo.lock references a dependency as `name`, `name version` or
+/// `name version (source)`, only adding what is needed to disambiguate.
+fn resolve_reference<'a>(lockfile: &'a Lockfile, reference: &str) -> Option<&'a LockedPackage> {
+    let mut parts = reference.split_whitespace();
+    let name = parts.next()?;
+    let version = parts.next();
+
+    lockfile.package.iter().find(|package| {
+        package.name == name && version.is_none_or(|version| package.version == version)
+    })
+}
+
+fn dot_id(label: &str) -> String {
+    format!("\"{}\"", label.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const MANIFEST: &str = r#"
+[package]
+name = "sleigh"
+version = "0.1.0"
+
+[dependencies]
+reindeer = { version = "1", features = ["red-nose"] }
+
+[target.'cfg(windows)'.dependencies]
+chimney = "2"
+"#;
+
+    const LOCKFILE: &str = r#"
+[[package]]
+name = "sleigh"
+version = "0.1.0"
+dependencies = ["chimney", "reindeer"]
+
+[[package]]
+name = "reindeer"
+version = "1.2.0"
+dependencies = ["bells 0.1.0"]
+
+[[package]]
+name = "chimney"
+version = "2.0.1"
+dependencies = ["bells 0.2.0"]
+
+[[package]]
+name = "bells"
+version = "0.1.0"
+
+[[package]]
+name = "bells"
+version = "0.2.0"
+"#;
+
+    fn graph(lockfile: Option<&str>) -> Result<DependencyGraph, ManifestParseError> {
+        let manifest: Manifest = toml::from_str(MANIFEST).unwrap();
+        let lockfile: Option<Lockfile> = lockfile.map(|lockfile| toml::from_str(lockfile).unwrap());
+
+        DependencyGraph::build(&manifest, lockfile.as_ref())
+    }
+
+    #[test]
+    fn reports_the_resolved_graph_as_json() {
+        let graph = serde_json::to_value(graph(Some(LOCKFILE)).unwrap()).unwrap();
+
+        assert_eq!(graph["root"], json!({ "name": "sleigh", "version": "0.1.0" }));
+        assert_eq!(graph["resolved"], json!(true));
+        assert_eq!(graph["direct"], json!([
+            {
+                "name": "reindeer",
+                "package": "reindeer",
+                "kind": "normal",
+                "target": null,
+                "requirement": "1",
+                "resolved_version": "1.2.0",
+                "optional": false,
+                "default_features": true,
+                "features": ["red-nose"]
+            },
+            {
+                "name": "chimney",
+                "package": "chimney",
+                "kind": "normal",
+                "target": "cfg(windows)",
+                "requirement": "2",
+                "resolved_version": "2.0.1",
+                "optional": false,
+                "default_features": true,
+                "features": []
+            }
+        ]));
+        assert_eq!(graph["transitive"], json!([
+            { "name": "bells", "version": "0.1.0" },
+            { "name": "bells", "version": "0.2.0" }
+        ]));
+        assert_eq!(graph["duplicates"], json!([{ "name": "bells", "versions": ["0.1.0", "0.2.0"] }]));
+        assert_eq!(graph["edges"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn draws_the_graph_as_dot() {
+        assert_eq!(graph(Some(LOCKFILE)).unwrap().to_dot(), [
+            "digraph dependencies {",
+            r#"    "sleigh 0.1.0" [shape=box, style=bold];"#,
+            r#"    "bells 0.1.0" [color=red];"#,
+            r#"    "bells 0.2.0" [color=red];"#,
+            r#"    "sleigh 0.1.0" -> "chimney 2.0.1";"#,
+            r#"    "sleigh 0.1.0" -> "reindeer 1.2.0" [label="red-nose"];"#,
+            r#"    "chimney 2.0.1" -> "bells 0.2.0";"#,
+            r#"    "reindeer 1.2.0" -> "bells 0.1.0";"#,
+            "}"
+        ].join("\n"));
+    }
+
+    #[test]
+    fn links_requirements_without_a_lockfile() {
+        let graph = graph(None).unwrap();
+
+        assert!(!graph.resolved);
+        assert_eq!(graph.to_dot(), [
+            "digraph dependencies {",
+            r#"    "sleigh 0.1.0" [shape=box, style=bold];"#,
+            r#"    "sleigh 0.1.0" -> "reindeer 1" [label="red-nose"];"#,
+            r#"    "sleigh 0.1.0" -> "chimney 2";"#,
+            "}"
+        ].join("\n"));
+    }
+
+    #[test]
+    fn rejects_lockfiles_referencing_unlisted_packages() {
+        let lockfile = LOCKFILE.replace("\"bells 0.2.0\"", "\"tinsel\"");
+
+        let error = graph(Some(&lockfile)).err().unwrap();
+        assert_eq!(error.message, "Cargo.lock dependency of chimney 2.0.1 isn't listed: tinsel");
+    }
+}
@@ -0,0 +1,98 @@
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::Serialize;
+
+use super::format::ManifestFormat;
+
+#[derive(Serialize, Debug)]
+pub struct ManifestParseError {
+    pub format: &'static str,
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub snippet: Option<String>
+}
+
+impl ManifestParseError {
+    pub fn from_json(error: &serde_json::Error, source: &str) -> Self {
+        Self::at(ManifestFormat::Json, error.to_string(), error.line(), error.column(), source)
+    }
+
+    pub fn from_yaml(error: &serde_yaml::Error, source: &str) -> Self {
+        match error.location() {
+            Some(location) => Self::at(
+                ManifestFormat::Yaml,
+                error.to_string(),
+                location.line(),
+                location.column(),
+                source
+            ),
+            None => Self::without_location(ManifestFormat::Yaml, error.to_string())
+        }
+    }
+
+    pub fn from_toml(error: &toml::de::Error, source: &str) -> Self {
+        match error.span() {
+            Some(span) => {
+                let (line, column) = line_and_column(source, span.start);
+                Self::at(ManifestFormat::Toml, error.message().to_string(), line, column, source)
+            },
+            None => Self::without_location(ManifestFormat::Toml, error.message().to_string())
+        }
+    }
+
+    pub fn without_location(format: ManifestFormat, message: String) -> Self {
+        Self {
+            format: format.as_str(),
+            message,
+            line: None,
+            column: None,
+            snippet: None
+        }
+    }
+
+    /// `line` and `column` are 1-based, as reported by serde_json and serde_yaml.
+    fn at(
+        format: ManifestFormat,
+        message: String,
+        line: usize,
+        column: usize,
+        source: &str
+    ) -> Self {
+        if line == 0 {
+            return Self::without_location(format, message)
+        }
+
+        let snippet = source.lines().nth(line - 1).map(|source_line| {
+            let caret_offset = source_line.chars().take(column.saturating_sub(1)).count();
+            format!("{}\n{}^", source_line, " ".repeat(caret_offset))
+        });
+
+        Self {
+            format: format.as_str(),
+            message,
+            line: Some(line),
+            column: Some(column),
+            snippet
+        }
+    }
+}
+
+impl IntoResponse for ManifestParseError {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, Json(self)).into_response()
+    }
+}
+
+/// Converts a byte offset into a 1-based (line, column) pair.
+fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+
+    (line, column)
+}
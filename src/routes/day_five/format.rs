@@ -0,0 +1,122 @@
+use mime::Mime;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ManifestFormat {
+    Json,
+    Yaml,
+    Toml
+}
+
+impl ManifestFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ManifestFormat::Json => "json",
+            ManifestFormat::Yaml => "yaml",
+            ManifestFormat::Toml => "toml",
+        }
+    }
+
+    /// Resolves a Content-Type header value into a manifest format, taking
+    /// parameters, `+json`/`+yaml`/`+toml` suffixes and the usual `x-`/`text/`
+    /// aliases into account. Returns `None` for anything we can't handle,
+    /// including non UTF-8 charsets.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime: Mime = content_type.trim().parse().ok()?;
+
+        if let Some(charset) = mime.get_param(mime::CHARSET) {
+            let charset = charset.as_str().to_ascii_lowercase();
+            if charset != "utf-8" && charset != "utf8" && charset != "us-ascii" {
+                return None
+            }
+        }
+
+        if let Some(suffix) = mime.suffix() {
+            return Self::from_name(suffix.as_str())
+        }
+
+        match (mime.type_().as_str(), mime.subtype().as_str()) {
+            ("application", subtype) | ("text", subtype) => {
+                Self::from_name(subtype.strip_prefix("x-").unwrap_or(subtype))
+            },
+            _ => None
+        }
+    }
+
+    /// Guesses the format of a body sent without a Content-Type. JSON is tried
+    /// first since every JSON document is also valid YAML.
+    pub fn sniff(body: &str) -> Option<Self> {
+        let trimmed = body.trim_start();
+
+        if (trimmed.starts_with('{') || trimmed.starts_with('['))
+            && serde_json::from_str::<serde_json::Value>(body).is_ok()
+        {
+            return Some(ManifestFormat::Json)
+        }
+
+        if toml::from_str::<toml::Table>(body).is_ok_and(|table| !table.is_empty()) {
+            return Some(ManifestFormat::Toml)
+        }
+
+        match serde_yaml::from_str::<serde_yaml::Value>(body) {
+            Ok(serde_yaml::Value::Mapping(_)) => Some(ManifestFormat::Yaml),
+            _ => None
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(ManifestFormat::Json),
+            "yaml" | "yml" => Some(ManifestFormat::Yaml),
+            "toml" => Some(ManifestFormat::Toml),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_content_types() {
+        let cases = [
+            ("application/json", Some(ManifestFormat::Json)),
+            ("Application/JSON; charset=UTF-8", Some(ManifestFormat::Json)),
+            ("application/manifest+json", Some(ManifestFormat::Json)),
+            ("application/vnd.cargo+toml; charset=utf8", Some(ManifestFormat::Toml)),
+            ("application/x-yaml", Some(ManifestFormat::Yaml)),
+            ("text/yaml", Some(ManifestFormat::Yaml)),
+            ("text/x-yml", Some(ManifestFormat::Yaml)),
+            ("application/toml", Some(ManifestFormat::Toml)),
+            ("text/x-toml; charset=us-ascii", Some(ManifestFormat::Toml)),
+            ("application/json; charset=latin1", None),
+            ("text/yaml; charset=utf-16", None),
+            ("application/manifest+xml", None),
+            ("image/json", None),
+            ("text/plain", None),
+            ("not a mime", None)
+        ];
+
+        for (content_type, format) in cases {
+            assert_eq!(ManifestFormat::from_content_type(content_type), format, "{}", content_type);
+        }
+    }
+
+    #[test]
+    fn sniffs_bodies() {
+        let cases = [
+            (r#"{"package": {"name": "x"}}"#, Some(ManifestFormat::Json)),
+            ("  [1, 2]", Some(ManifestFormat::Json)),
+            ("[package]\nname = \"x\"\n", Some(ManifestFormat::Toml)),
+            ("package:\n  name: x\n", Some(ManifestFormat::Yaml)),
+            ("{not json: but yaml}", Some(ManifestFormat::Yaml)),
+            ("", None),
+            ("just words", None),
+            ("- a list\n- in yaml\n", None)
+        ];
+
+        for (body, format) in cases {
+            assert_eq!(ManifestFormat::sniff(body), format, "{:?}", body);
+        }
+    }
+}
@@ -1,9 +1,6 @@
-use axum::http::StatusCode;
 use serde::Deserialize;
 
-use crate::routes::day_five::verify_keywords;
-
-use super::Metadata;
+use super::{diagnostics::ManifestParseError, policy::ManifestPackage, ManifestError, Metadata, ParsedManifest};
 
 #[derive(Deserialize, Debug)]
 struct Manifest {
@@ -14,40 +11,30 @@ struct Manifest {
 struct Package {
     metadata: Metadata,
     #[serde(rename = "rust-version")]
-    #[allow(dead_code)]
     rust_version: Option<String>,
-    keywords: Option<Vec<String>>
+    keywords: Option<Vec<String>>,
+    categories: Option<Vec<String>>,
+    license: Option<String>
 }
 
 
 pub async fn process_json_manifest(
     body: String
-) -> Result<(StatusCode, String), StatusCode> {
-
-    let manifest: Manifest = match serde_json::from_str(&body) {
-        Ok(json) => json,
-        Err(e) => {
-            println!("error: {:?}", e);
-            return Ok((StatusCode::BAD_REQUEST, "Invalid manifest".to_string()))
-        }
-    };
-    println!("manifest json: {:?}", manifest);
-
-    let metadata: Metadata = manifest.package.metadata;
+) -> Result<ParsedManifest, ManifestError> {
 
-    let return_string = metadata.get_orders_string()?;
-
-    match manifest.package.keywords {
-        Some(keywords) => {
-            match verify_keywords(keywords) {
-                Ok(_) => (),
-                Err(e) => return Ok(e)
-            };
-        }
-        None => return Ok((StatusCode::BAD_REQUEST, "Magic keyword not provided".to_string()))
-    }
+    let manifest: Manifest = serde_json::from_str(&body)
+        .map_err(|e| ManifestParseError::from_json(&e, &body))?;
+    println!("manifest json: {:?}", manifest);
 
-    Ok(
-        (StatusCode::OK, return_string)
-    )
-}
\ No newline at end of file
+    let package = manifest.package;
+
+    Ok(ParsedManifest {
+        package: ManifestPackage {
+            keywords: package.keywords,
+            categories: package.categories,
+            license: package.license,
+            rust_version: package.rust_version
+        },
+        metadata: Some(package.metadata)
+    })
+}
@@ -1,12 +1,86 @@
-use axum::http::{header::CONTENT_TYPE, HeaderMap, StatusCode};
+use std::sync::Arc;
+use axum::{extract::State, http::{header::{ACCEPT, CONTENT_TYPE}, HeaderMap, HeaderValue, StatusCode}, response::{IntoResponse, Response}, Json};
+use diagnostics::ManifestParseError;
+use format::ManifestFormat;
 use json_manifest::process_json_manifest;
+use policy::{ManifestPackage, PolicyViolation, REQUIRED_KEYWORDS_RULE};
 use serde::Deserialize;
+use serde_json::json;
 use toml_manifest::process_toml_manifest;
 use yaml_manifest::process_yaml_manifest;
 
+use crate::AppState;
+
 pub mod toml_manifest;
 pub mod yaml_manifest;
 pub mod json_manifest;
+pub mod format;
+pub mod diagnostics;
+pub mod policy;
+pub mod dependency_graph;
+
+/// Response header reporting which format the manifest was processed as.
+pub const MANIFEST_FORMAT_HEADER: &str = "x-manifest-format";
+
+/// Body of a missing magic keyword, kept as is for existing clients.
+const MAGIC_KEYWORD_MESSAGE: &str = "Magic keyword not provided";
+
+/// Policy violations are plain text unless the client accepts JSON, in which
+/// case they're reported as `{"violations": [...]}`. In plain text, each line
+/// names the violated rule, except for the magic keyword answer.
+#[derive(Debug)]
+pub enum ManifestError {
+    Status(StatusCode),
+    Parse(ManifestParseError),
+    Policy(Vec<PolicyViolation>),
+    PolicyReport(Vec<PolicyViolation>)
+}
+
+impl From<StatusCode> for ManifestError {
+    fn from(status: StatusCode) -> Self {
+        ManifestError::Status(status)
+    }
+}
+
+impl From<ManifestParseError> for ManifestError {
+    fn from(error: ManifestParseError) -> Self {
+        ManifestError::Parse(error)
+    }
+}
+
+impl IntoResponse for ManifestError {
+    fn into_response(self) -> Response {
+        match self {
+            ManifestError::Status(status) => status.into_response(),
+            ManifestError::Parse(error) => error.into_response(),
+            ManifestError::Policy(violations) => {
+                let mut lines = Vec::new();
+
+                if violations.iter().any(|violation| violation.rule == REQUIRED_KEYWORDS_RULE) {
+                    lines.push(MAGIC_KEYWORD_MESSAGE.to_string());
+                }
+
+                lines.extend(violations.into_iter()
+                    .filter(|violation| violation.rule != REQUIRED_KEYWORDS_RULE)
+                    .map(|violation| format!("{}: {}", violation.rule, violation.message)));
+
+                (StatusCode::BAD_REQUEST, lines.join("\n")).into_response()
+            },
+            ManifestError::PolicyReport(violations) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "violations": violations }))
+            ).into_response()
+        }
+    }
+}
+
+/// A manifest as parsed by one of the format specific processors, before the
+/// manifest policy is applied.
+#[derive(Debug)]
+pub struct ParsedManifest {
+    pub package: ManifestPackage,
+    pub metadata: Option<Metadata>
+}
 
 #[derive(Deserialize, Debug)]
 pub struct Metadata {
@@ -69,34 +143,115 @@ impl Order {
 }
 
 pub async fn process_manifest(
+    State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     body: String,
-) -> Result<(StatusCode, String), StatusCode> {
-    let content_type: &axum::http::HeaderValue = match headers.get(CONTENT_TYPE) {
-        Some(content_type) => content_type,
-        None => return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE)
-    };
+) -> Result<(StatusCode, HeaderMap, String), ManifestError> {
+    let format = match headers.get(CONTENT_TYPE) {
+        Some(content_type) => {
+            let content_type_str = content_type.to_str()
+                .map_err(|_| StatusCode::UNSUPPORTED_MEDIA_TYPE)?;
 
-    match content_type.to_str() {
-        Ok(content_type_str) => {
-            match content_type_str {
-                "application/json" => Ok(process_json_manifest(body).await?),
-                "application/yaml" => Ok(process_yaml_manifest(body).await?),
-                "application/toml" => Ok(process_toml_manifest(body).await?),
-                _ => return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE)
-            }
+            ManifestFormat::from_content_type(content_type_str)
+                .ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?
         },
-        Err(_) => return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        None => ManifestFormat::sniff(&body).ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?
+    };
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        MANIFEST_FORMAT_HEADER,
+        HeaderValue::from_static(format.as_str())
+    );
+
+    let manifest = match format {
+        ManifestFormat::Json => process_json_manifest(body).await?,
+        ManifestFormat::Yaml => process_yaml_manifest(body).await?,
+        ManifestFormat::Toml => process_toml_manifest(body).await?,
+    };
+
+    let violations = state.manifest_policy.evaluate(&manifest.package);
+    if !violations.is_empty() {
+        return Err(match accepts_json(&headers) {
+            true => ManifestError::PolicyReport(violations),
+            false => ManifestError::Policy(violations)
+        })
     }
+
+    let metadata = manifest.metadata.ok_or(StatusCode::NO_CONTENT)?;
+    let response_body = metadata.get_orders_string()?;
+
+    Ok((StatusCode::OK, response_headers, response_body))
+}
+
+fn accepts_json(headers: &HeaderMap) -> bool {
+    headers.get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_type| {
+            let essence = media_type.split(';').next().unwrap_or_default().trim();
+            essence.eq_ignore_ascii_case("application/json")
+        })
 }
 
-pub fn verify_keywords(
-    keywords: Vec<String>
-) -> Result<(), (StatusCode, String)> {
-    if !keywords.contains(&"Christmas 2024".to_string()) {
-        return Err((StatusCode::BAD_REQUEST, "Magic keyword not provided".to_string()))
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+
+    use super::*;
+    use policy::ManifestPolicy;
+
+    async fn body(error: ManifestError) -> (StatusCode, String) {
+        let response = error.into_response();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
     }
 
-    Ok(())
-}
+    #[tokio::test]
+    async fn missing_magic_keyword_keeps_the_plain_text_answer() {
+        let violations = || ManifestPolicy::default().evaluate(&ManifestPackage::default());
+
+        assert_eq!(
+            body(ManifestError::Policy(violations())).await,
+            (StatusCode::BAD_REQUEST, MAGIC_KEYWORD_MESSAGE.to_string())
+        );
+
+        let (status, report) = body(ManifestError::PolicyReport(violations())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(report.contains(r#""rule":"required-keywords""#));
+    }
+
+    #[tokio::test]
+    async fn plain_text_violations_name_their_rules() {
+        let policy = ManifestPolicy {
+            forbidden_keywords: vec!["grinch".to_string()],
+            license_allowlist: Some(vec!["MIT".to_string()]),
+            ..ManifestPolicy::default()
+        };
+        let package = ManifestPackage {
+            keywords: Some(vec!["grinch".to_string()]),
+            ..ManifestPackage::default()
+        };
 
+        assert_eq!(
+            body(ManifestError::Policy(policy.evaluate(&package))).await,
+            (StatusCode::BAD_REQUEST, [
+                MAGIC_KEYWORD_MESSAGE,
+                "forbidden-keywords: Forbidden keyword used: grinch",
+                "license-allowlist: License not provided"
+            ].join("\n"))
+        );
+    }
+
+    #[test]
+    fn negotiates_json_on_accept() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_json(&headers));
+
+        headers.insert(ACCEPT, HeaderValue::from_static("text/plain, application/json;q=0.5"));
+        assert!(accepts_json(&headers));
+    }
+}
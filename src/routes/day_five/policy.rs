@@ -0,0 +1,288 @@
+use std::{fs, io::ErrorKind, path::Path};
+use serde::{Deserialize, Serialize};
+
+pub const MANIFEST_POLICY_PATH: &str = "manifest_policy.toml";
+
+/// Rule reported for each required keyword a manifest lacks.
+pub const REQUIRED_KEYWORDS_RULE: &str = "required-keywords";
+
+/// The package fields policies are evaluated against, extracted the same way
+/// from JSON, YAML and TOML manifests.
+#[derive(Debug, Default)]
+pub struct ManifestPackage {
+    pub keywords: Option<Vec<String>>,
+    pub categories: Option<Vec<String>>,
+    pub license: Option<String>,
+    pub rust_version: Option<String>
+}
+
+#[derive(Serialize, Debug)]
+pub struct PolicyViolation {
+    pub rule: &'static str,
+    pub message: String
+}
+
+impl PolicyViolation {
+    fn new(rule: &'static str, message: String) -> Self {
+        Self { rule, message }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ManifestPolicy {
+    #[serde(default)]
+    pub required_keywords: Vec<String>,
+    #[serde(default)]
+    pub forbidden_keywords: Vec<String>,
+    #[serde(default)]
+    pub required_categories: Vec<String>,
+    pub license_allowlist: Option<Vec<String>>,
+    pub minimum_rust_version: Option<String>
+}
+
+impl Default for ManifestPolicy {
+    fn default() -> Self {
+        Self {
+            required_keywords: vec!["Christmas 2024".to_string()],
+            forbidden_keywords: Vec::new(),
+            required_categories: Vec::new(),
+            license_allowlist: None,
+            minimum_rust_version: None
+        }
+    }
+}
+
+impl ManifestPolicy {
+    /// Loads the policy from a TOML file, falling back to the default policy
+    /// (just the magic keyword) when the file doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                println!("No manifest policy at {}, using defaults", path.display());
+                return Ok(Self::default())
+            },
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e))
+        };
+
+        let policy: Self = toml::from_str(&contents)
+            .map_err(|e| format!("Invalid manifest policy in {}: {}", path.display(), e))?;
+
+        if let Some(minimum) = &policy.minimum_rust_version {
+            parse_rust_version(minimum)
+                .ok_or(format!("Invalid minimum-rust-version in {}: {}", path.display(), minimum))?;
+        }
+
+        Ok(policy)
+    }
+
+    pub fn evaluate(&self, package: &ManifestPackage) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        let keywords = package.keywords.as_deref().unwrap_or_default();
+        let categories = package.categories.as_deref().unwrap_or_default();
+
+        for keyword in self.required_keywords.iter() {
+            if !keywords.contains(keyword) {
+                violations.push(PolicyViolation::new(
+                    REQUIRED_KEYWORDS_RULE,
+                    format!("Magic keyword not provided: {}", keyword)
+                ));
+            }
+        }
+
+        for keyword in self.forbidden_keywords.iter() {
+            if keywords.contains(keyword) {
+                violations.push(PolicyViolation::new(
+                    "forbidden-keywords",
+                    format!("Forbidden keyword used: {}", keyword)
+                ));
+            }
+        }
+
+        for category in self.required_categories.iter() {
+            if !categories.contains(category) {
+                violations.push(PolicyViolation::new(
+                    "required-categories",
+                    format!("Required category missing: {}", category)
+                ));
+            }
+        }
+
+        if let Some(allowlist) = &self.license_allowlist {
+            match &package.license {
+                Some(license) if is_license_allowed(license, allowlist) => (),
+                Some(license) => violations.push(PolicyViolation::new(
+                    "license-allowlist",
+                    format!("License not allowed: {}", license)
+                )),
+                None => violations.push(PolicyViolation::new(
+                    "license-allowlist",
+                    "License not provided".to_string()
+                ))
+            }
+        }
+
+        if let Some(minimum) = &self.minimum_rust_version {
+            let required = parse_rust_version(minimum);
+            match &package.rust_version {
+                Some(version) => match parse_rust_version(version) {
+                    Some(actual) if Some(actual) >= required => (),
+                    Some(_) => violations.push(PolicyViolation::new(
+                        "minimum-rust-version",
+                        format!("rust-version {} is older than {}", version, minimum)
+                    )),
+                    None => violations.push(PolicyViolation::new(
+                        "minimum-rust-version",
+                        format!("rust-version is not a valid version: {}", version)
+                    ))
+                },
+                None => violations.push(PolicyViolation::new(
+                    "minimum-rust-version",
+                    format!("rust-version not provided, {} or newer is required", minimum)
+                ))
+            }
+        }
+
+        violations
+    }
+}
+
+/// SPDX expressions are accepted when at least one `OR` alternative consists
+/// solely of allowed licenses. The legacy `/` separator is treated as `OR`.
+fn is_license_allowed(license: &str, allowlist: &[String]) -> bool {
+    license
+        .replace('/', " OR ")
+        .split(" OR ")
+        .any(|alternative| {
+            alternative
+                .split(" AND ")
+                .map(|id| id.trim().trim_matches(|c| c == '(' || c == ')'))
+                .all(|id| allowlist.iter().any(|allowed| allowed == id))
+        })
+}
+
+/// Parses `major[.minor[.patch]]`, as used by the `rust-version` field.
+fn parse_rust_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().map(|part| part.parse().ok()).unwrap_or(Some(0))?;
+    let patch = parts.next().map(|part| part.parse().ok()).unwrap_or(Some(0))?;
+
+    if parts.next().is_some() {
+        return None
+    }
+
+    Some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package() -> ManifestPackage {
+        ManifestPackage {
+            keywords: Some(vec!["Christmas 2024".to_string(), "sleigh".to_string()]),
+            categories: Some(vec!["games".to_string()]),
+            license: Some("MIT".to_string()),
+            rust_version: Some("1.75".to_string())
+        }
+    }
+
+    fn rules(policy: &ManifestPolicy, package: &ManifestPackage) -> Vec<&'static str> {
+        policy.evaluate(package).into_iter().map(|violation| violation.rule).collect()
+    }
+
+    #[test]
+    fn requires_keywords() {
+        let policy = ManifestPolicy::default();
+
+        assert!(rules(&policy, &package()).is_empty());
+        assert_eq!(rules(&policy, &ManifestPackage::default()), vec![REQUIRED_KEYWORDS_RULE]);
+    }
+
+    #[test]
+    fn forbids_keywords() {
+        let policy = ManifestPolicy {
+            forbidden_keywords: vec!["sleigh".to_string(), "grinch".to_string()],
+            ..ManifestPolicy::default()
+        };
+
+        assert_eq!(rules(&policy, &package()), vec!["forbidden-keywords"]);
+    }
+
+    #[test]
+    fn requires_categories() {
+        let policy = ManifestPolicy {
+            required_categories: vec!["games".to_string(), "no-std".to_string()],
+            ..ManifestPolicy::default()
+        };
+
+        let violations = policy.evaluate(&package());
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "required-categories");
+        assert_eq!(violations[0].message, "Required category missing: no-std");
+    }
+
+    #[test]
+    fn allows_listed_licenses_only() {
+        let policy = ManifestPolicy {
+            license_allowlist: Some(vec!["MIT".to_string(), "Apache-2.0".to_string()]),
+            ..ManifestPolicy::default()
+        };
+        let licensed = |license: Option<&str>| ManifestPackage {
+            license: license.map(str::to_string),
+            ..package()
+        };
+
+        for license in ["MIT", "MIT OR GPL-3.0", "(MIT AND Apache-2.0)", "GPL-3.0/Apache-2.0"] {
+            assert!(rules(&policy, &licensed(Some(license))).is_empty(), "{}", license);
+        }
+
+        for license in [Some("GPL-3.0"), Some("MIT AND GPL-3.0"), None] {
+            assert_eq!(rules(&policy, &licensed(license)), vec!["license-allowlist"], "{:?}", license);
+        }
+    }
+
+    #[test]
+    fn requires_a_minimum_rust_version() {
+        let policy = ManifestPolicy {
+            minimum_rust_version: Some("1.70".to_string()),
+            ..ManifestPolicy::default()
+        };
+        let versioned = |version: Option<&str>| ManifestPackage {
+            rust_version: version.map(str::to_string),
+            ..package()
+        };
+
+        for version in ["1.70", "1.70.0", "1.75.2", "2"] {
+            assert!(rules(&policy, &versioned(Some(version))).is_empty(), "{}", version);
+        }
+
+        for version in [Some("1.69.9"), Some("1"), Some("1.x"), Some("1.70.0.1"), None] {
+            assert_eq!(rules(&policy, &versioned(version)), vec!["minimum-rust-version"], "{:?}", version);
+        }
+    }
+
+    #[test]
+    fn loads_policies() {
+        let path = std::env::temp_dir().join(format!("manifest_policy_{}.toml", std::process::id()));
+
+        assert_eq!(ManifestPolicy::load(&path).unwrap().required_keywords, vec!["Christmas 2024"]);
+
+        fs::write(&path, "required-keywords = []\nminimum-rust-version = \"1.70\"\n").unwrap();
+        let policy = ManifestPolicy::load(&path).unwrap();
+        assert!(policy.required_keywords.is_empty());
+        assert_eq!(policy.minimum_rust_version.as_deref(), Some("1.70"));
+
+        fs::write(&path, "minimum-rust-version = \"soon\"\n").unwrap();
+        assert!(ManifestPolicy::load(&path).unwrap_err().contains("minimum-rust-version"));
+
+        fs::write(&path, "required-keyword = []\n").unwrap();
+        assert!(ManifestPolicy::load(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,42 +1,26 @@
-
 use axum::http::StatusCode;
 use cargo_manifest::Manifest;
 
-use super::{verify_keywords, Metadata};
+use super::{diagnostics::ManifestParseError, policy::ManifestPackage, ManifestError, Metadata, ParsedManifest};
 
 pub async fn process_toml_manifest(
     body: String
-) -> Result<(StatusCode, String), StatusCode> {
-    
-    let cargo_manifest = Manifest::<Metadata>::from_slice_with_metadata(body.as_bytes());
+) -> Result<ParsedManifest, ManifestError> {
     
-    let manifest = match cargo_manifest {
-        Ok(manifest) => manifest,
-        Err(_) => return Ok((StatusCode::BAD_REQUEST, "Invalid manifest".to_string()))
-    };
+    // Deserializing through `toml` directly (which is all `from_slice_with_metadata`
+    // does for an in-memory manifest) keeps the error span around.
+    let manifest: Manifest<Metadata> = toml::from_str(&body)
+        .map_err(|e| ManifestParseError::from_toml(&e, &body))?;
 
     let package = manifest.package.ok_or(StatusCode::NO_CONTENT)?;
 
-    let keywords = match package.keywords {
-        Some(keywords) => match keywords.as_local() {
-            Some(keywords) => keywords,
-            None => return Ok((StatusCode::BAD_REQUEST, "Magic keyword not provided".to_string()))
+    Ok(ParsedManifest {
+        package: ManifestPackage {
+            keywords: package.keywords.and_then(|keywords| keywords.as_local()),
+            categories: package.categories.and_then(|categories| categories.as_local()),
+            license: package.license.and_then(|license| license.as_local()),
+            rust_version: package.rust_version.and_then(|rust_version| rust_version.as_local())
         },
-        None => return Ok((StatusCode::BAD_REQUEST, "Magic keyword not provided".to_string()))
-    };
-
-    match verify_keywords(keywords) {
-        Ok(_) => (),
-        Err(e) => return Ok(e)
-    };
-
-    let metadata = package.metadata.ok_or(StatusCode::NO_CONTENT)?;
-    
-    let return_string = metadata.get_orders_string()?;
-
-    Ok(
-        (StatusCode::OK, return_string)
-    )
+        metadata: package.metadata
+    })
 }
-
-
@@ -1,7 +1,6 @@
-use axum::http::StatusCode;
 use serde::Deserialize;
 
-use super::Metadata;
+use super::{diagnostics::ManifestParseError, format::ManifestFormat, policy::ManifestPackage, ManifestError, Metadata, ParsedManifest};
 
 #[derive(Deserialize, Debug)]
 
@@ -14,32 +13,35 @@ struct Package {
     metadata: Metadata,
     #[serde(rename = "rust-version")]
     rust_version: Option<String>,
+    keywords: Option<Vec<String>>,
+    categories: Option<Vec<String>>,
+    license: Option<String>
 }
 
 
 pub async fn process_yaml_manifest(
     body: String
-) -> Result<(StatusCode, String), StatusCode> {
-
-    let manifest: Manifest = match serde_yaml::from_str(&body) {
-        Ok(yaml) => yaml,
-        Err(e) => {
-            println!("error: {:?}", e);
-            return Ok((StatusCode::BAD_REQUEST, "Invalid manifest".to_string()))
-        }
-    };
-    
-    let rust_version_option = manifest.package.rust_version;
-    if rust_version_option == Some("true".to_string()) || rust_version_option == Some("false".to_string()) {
-        return Ok((StatusCode::BAD_REQUEST, "Invalid manifest".to_string()))
-    }
-
-    let metadata: Metadata = manifest.package.metadata;
+) -> Result<ParsedManifest, ManifestError> {
 
+    let manifest: Manifest = serde_yaml::from_str(&body)
+        .map_err(|e| ManifestParseError::from_yaml(&e, &body))?;
+    let package = manifest.package;
 
-    let return_string = metadata.get_orders_string()?;
+    let rust_version_option = package.rust_version;
+    if rust_version_option == Some("true".to_string()) || rust_version_option == Some("false".to_string()) {
+        return Err(ManifestParseError::without_location(
+            ManifestFormat::Yaml,
+            "package.rust-version: invalid type: boolean, expected a string".to_string()
+        ).into())
+    }
 
-    Ok(
-        (StatusCode::OK, return_string)
-    )
-}
\ No newline at end of file
+    Ok(ParsedManifest {
+        package: ManifestPackage {
+            keywords: package.keywords,
+            categories: package.categories,
+            license: package.license,
+            rust_version: rust_version_option
+        },
+        metadata: Some(package.metadata)
+    })
+}
@@ -0,0 +1,250 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::JsonRejection, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    Json
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{
+    collections::Collection,
+    error::QuoteError,
+    validation::FieldError,
+    Quote
+};
+use crate::{
+    routes::{
+        admin::{constant_time_eq, require_admin},
+        day_sixteen::{claims::{ClaimOptions, QUOTES_AUDIENCE}, sign, verify_bearer}
+    },
+    AppState
+};
+
+/// Name recorded for changes made with the admin token.
+pub const ADMIN_USER: &str = "admin";
+
+const ADMIN_ROLE: &str = "admin";
+const USER_ROLE: &str = "user";
+
+/// Who is calling, from an `Authorization: Bearer` header holding either a
+/// token issued by `/19/tokens` or the admin token.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub user: String,
+    pub admin: bool
+}
+
+/// `expires_in` is in seconds, the gift token default when missing.
+#[derive(Deserialize, Debug)]
+pub struct TokenForIssue {
+    pub user: String,
+    #[serde(default)]
+    pub admin: bool,
+    pub expires_in: Option<u64>
+}
+
+#[derive(Serialize, Debug)]
+pub struct IssuedToken {
+    pub token: String,
+    pub user: String,
+    pub admin: bool,
+    pub expires_at: DateTime<Utc>
+}
+
+impl Caller {
+    /// Owners and admins can change a quote. Quotes without an owner are
+    /// admin only.
+    pub fn authorize(&self, quote: &Quote) -> Result<(), StatusCode> {
+        if self.owns(quote.owner.as_deref()) {
+            return Ok(())
+        }
+
+        println!("{} can't change quote {} owned by {:?}", self.user, quote.id, quote.owner);
+        Err(StatusCode::FORBIDDEN)
+    }
+
+    /// Same rules as quotes.
+    pub fn authorize_collection(&self, collection: &Collection) -> Result<(), StatusCode> {
+        if self.owns(collection.owner.as_deref()) {
+            return Ok(())
+        }
+
+        println!(
+            "{} can't change collection {} owned by {:?}",
+            self.user, collection.id, collection.owner
+        );
+        Err(StatusCode::FORBIDDEN)
+    }
+
+    fn owns(&self, owner: Option<&str>) -> bool {
+        self.admin || owner == Some(self.user.as_str())
+    }
+
+    pub fn require_admin(&self) -> Result<(), StatusCode> {
+        if self.admin {
+            return Ok(())
+        }
+
+        println!("{} isn't an admin", self.user);
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Authenticates the caller and checks they can change quote `id`, trashed
+/// or not. Only admins get past a quote that doesn't exist (anymore), others
+/// get a 404.
+pub async fn authorize_change(
+    state: &AppState,
+    headers: &HeaderMap,
+    id: &Uuid
+) -> Result<Caller, StatusCode> {
+    let caller = authenticate(state, headers).await?;
+
+    let quote = state.quote_repository
+        .find_quote(id)
+        .await
+        .map_err(|e| {
+            println!("Error fetching quote: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    match quote {
+        Some(quote) => caller.authorize(&quote)?,
+        None if caller.admin => (),
+        None => return Err(StatusCode::NOT_FOUND)
+    }
+
+    Ok(caller)
+}
+
+/// Authenticates the caller and checks they can change collection `id`.
+pub async fn authorize_collection_change(
+    state: &AppState,
+    headers: &HeaderMap,
+    id: &Uuid
+) -> Result<Caller, StatusCode> {
+    let caller = authenticate(state, headers).await?;
+
+    let collection = state.quote_repository
+        .get_collection(id)
+        .await
+        .map_err(|e| {
+            println!("Error fetching collection: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    caller.authorize_collection(&collection.collection)?;
+
+    Ok(caller)
+}
+
+/// The caller of a draft, `None` for anonymous drafts when they are allowed.
+/// A token that is there but invalid is still refused.
+pub async fn authenticate_draft(
+    state: &AppState,
+    headers: &HeaderMap
+) -> Result<Option<Caller>, StatusCode> {
+    if state.quotes_config.auth.anonymous_drafts && !headers.contains_key(AUTHORIZATION) {
+        return Ok(None)
+    }
+
+    authenticate(state, headers).await.map(Some)
+}
+
+/// Admins only, unless `admin-reset` is turned off. `None` for anonymous
+/// callers then.
+pub async fn authorize_reset(
+    state: &AppState,
+    headers: &HeaderMap
+) -> Result<Option<Caller>, StatusCode> {
+    if !state.quotes_config.auth.admin_reset {
+        return match headers.contains_key(AUTHORIZATION) {
+            true => authenticate(state, headers).await.map(Some),
+            false => Ok(None)
+        }
+    }
+
+    let caller = authenticate(state, headers).await?;
+    caller.require_admin()?;
+
+    Ok(Some(caller))
+}
+
+pub async fn authenticate(
+    state: &AppState,
+    headers: &HeaderMap
+) -> Result<Caller, StatusCode> {
+    let token = headers.get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if state.admin_token.as_deref()
+        .is_some_and(|admin_token| constant_time_eq(token.as_bytes(), admin_token.as_bytes()))
+    {
+        return Ok(Caller {
+            user: ADMIN_USER.to_string(),
+            admin: true
+        })
+    }
+
+    let claims = verify_bearer(state, token, QUOTES_AUDIENCE).await?;
+
+    let user = claims.sub.ok_or_else(|| {
+        println!("Quote token {} has no subject", claims.jti);
+        StatusCode::UNAUTHORIZED
+    })?;
+
+    Ok(Caller {
+        user,
+        admin: claims.gift.get("role").and_then(|role| role.as_str()) == Some(ADMIN_ROLE)
+    })
+}
+
+/// Admin only. Tokens can be revoked through `/16/revoke` like gift tokens.
+#[axum::debug_handler]
+pub async fn issue_token(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Result<Json<TokenForIssue>, JsonRejection>
+) -> Result<(StatusCode, Json<IssuedToken>), QuoteError> {
+    require_admin(&headers, state.admin_token.as_deref())?;
+
+    let Json(body) = body.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let user = body.user.trim().to_string();
+
+    if user.is_empty() || user.chars().any(char::is_control) {
+        return Err(vec![
+            FieldError::new("user", "can't be empty or contain control characters".to_string())
+        ].into())
+    }
+
+    let keyring = state.gift_keyring.lock().await;
+
+    let options = ClaimOptions {
+        expires_in: body.expires_in,
+        aud: Some(QUOTES_AUDIENCE.to_string()),
+        iss: keyring.validation.issuers.first().cloned(),
+        sub: Some(user.clone()),
+        ..ClaimOptions::default()
+    };
+
+    let role = if body.admin { ADMIN_ROLE } else { USER_ROLE };
+    let claims = options.into_claims(json!({ "role": role }), &keyring.validation)?;
+    let token = sign(&keyring, &claims)?;
+
+    let expires_at = DateTime::<Utc>::from_timestamp(claims.exp as i64, 0)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    Ok((StatusCode::CREATED, Json(IssuedToken {
+        token,
+        user,
+        admin: body.admin,
+        expires_at
+    })))
+}
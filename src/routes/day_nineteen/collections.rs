@@ -0,0 +1,194 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{
+    extract::{rejection::JsonRejection, Path, State},
+    http::{HeaderMap, StatusCode},
+    Json
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+use super::{
+    auth::{authenticate, authorize_collection_change},
+    error::QuoteError,
+    validation::{validate_collection_name, FieldError},
+    Quote
+};
+use crate::AppState;
+
+#[derive(FromRow, Debug, Clone, Serialize)]
+pub struct Collection {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// `None` for collections created before collections had owners.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>
+}
+
+/// `size` counts trashed quotes too, since they come back when restored.
+#[derive(FromRow, Debug, Serialize)]
+pub struct CollectionSummary {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub collection: Collection,
+    pub size: i64
+}
+
+/// A collection with its live quotes, in the collection's order.
+#[derive(Debug, Serialize)]
+pub struct CollectionWithQuotes {
+    #[serde(flatten)]
+    pub collection: Collection,
+    pub quotes: Vec<Quote>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CollectionForCreation {
+    pub name: String,
+    #[serde(default)]
+    pub quotes: Vec<Uuid>
+}
+
+/// Renames the collection and/or replaces its quotes, in the given order.
+#[derive(Deserialize, Debug)]
+pub struct CollectionForUpdate {
+    pub name: Option<String>,
+    pub quotes: Option<Vec<Uuid>>
+}
+
+pub enum CollectionOutcome {
+    Saved(CollectionWithQuotes),
+    NotFound,
+    UnknownQuotes(Vec<Uuid>)
+}
+
+impl CollectionForCreation {
+    pub fn validate(self) -> Result<Self, Vec<FieldError>> {
+        let name = validate_collection_name(&self.name);
+        let quotes = check_unique(&self.quotes);
+
+        match (name, quotes) {
+            (Ok(name), Ok(())) => Ok(Self { name, ..self }),
+            (name, quotes) => Err(name.err().into_iter().flatten().chain(quotes.err()).collect())
+        }
+    }
+}
+
+impl CollectionForUpdate {
+    pub fn validate(self) -> Result<Self, Vec<FieldError>> {
+        let name = self.name.as_deref().map(validate_collection_name).transpose();
+        let quotes = self.quotes.as_deref().map(check_unique).transpose();
+
+        match (name, quotes) {
+            (Ok(name), Ok(_)) => Ok(Self { name, ..self }),
+            (name, quotes) => Err(name.err().into_iter().flatten().chain(quotes.err()).collect())
+        }
+    }
+}
+
+fn check_unique(quotes: &[Uuid]) -> Result<(), FieldError> {
+    let mut seen = HashSet::new();
+
+    match quotes.iter().find(|id| !seen.insert(*id)) {
+        Some(id) => Err(FieldError::new("quotes", format!("lists {} more than once", id))),
+        None => Ok(())
+    }
+}
+
+impl CollectionOutcome {
+    fn into_result(self) -> Result<CollectionWithQuotes, QuoteError> {
+        match self {
+            CollectionOutcome::Saved(collection) => Ok(collection),
+            CollectionOutcome::NotFound => Err(StatusCode::NOT_FOUND.into()),
+            CollectionOutcome::UnknownQuotes(ids) => Err(ids.iter()
+                .map(|id| FieldError::new("quotes", format!("{} is not a live quote", id)))
+                .collect::<Vec<_>>()
+                .into())
+        }
+    }
+}
+
+#[axum::debug_handler]
+pub async fn list_collections(
+    State(state): State<Arc<AppState>>
+) -> Result<Json<Vec<CollectionSummary>>, QuoteError> {
+    let collections = state.quote_repository
+        .list_collections()
+        .await?;
+
+    Ok(Json(collections))
+}
+
+/// The caller owns the new collection.
+#[axum::debug_handler]
+pub async fn create_collection(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Result<Json<CollectionForCreation>, JsonRejection>
+) -> Result<(StatusCode, Json<CollectionWithQuotes>), QuoteError> {
+    let caller = authenticate(&state, &headers).await?;
+
+    let Json(body) = body.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let body = body.validate()?;
+
+    let collection = state.quote_repository
+        .create_collection(&body, &caller.user)
+        .await?
+        .into_result()?;
+
+    Ok((StatusCode::CREATED, Json(collection)))
+}
+
+#[axum::debug_handler]
+pub async fn get_collection(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>
+) -> Result<Json<CollectionWithQuotes>, QuoteError> {
+    let collection = state.quote_repository
+        .get_collection(&id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(collection))
+}
+
+/// Owner or admin only.
+#[axum::debug_handler]
+pub async fn update_collection(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    body: Result<Json<CollectionForUpdate>, JsonRejection>
+) -> Result<Json<CollectionWithQuotes>, QuoteError> {
+    authorize_collection_change(&state, &headers, &id).await?;
+
+    let Json(body) = body.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let body = body.validate()?;
+
+    let collection = state.quote_repository
+        .update_collection(&id, &body)
+        .await?
+        .into_result()?;
+
+    Ok(Json(collection))
+}
+
+/// Owner or admin only.
+#[axum::debug_handler]
+pub async fn delete_collection(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap
+) -> Result<Json<Collection>, QuoteError> {
+    authorize_collection_change(&state, &headers, &id).await?;
+
+    let collection = state.quote_repository
+        .delete_collection(&id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(collection))
+}
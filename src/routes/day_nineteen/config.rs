@@ -0,0 +1,233 @@
+use std::{fs, io::ErrorKind, path::{Path, PathBuf}};
+use serde::Deserialize;
+
+pub const QUOTES_CONFIG_PATH: &str = "quotes.toml";
+
+/// A century, so the purge cut-off stays well within what `DateTime` can
+/// represent.
+const MAX_RETENTION: u64 = 100 * 365 * 24 * 3600;
+
+/// Confirming a reset is meant to follow right after asking for it.
+const MAX_RESET_CONFIRMATION_TTL: u64 = 24 * 3600;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct QuotesConfig {
+    #[serde(default)]
+    pub limits: QuoteLimits,
+    #[serde(default)]
+    pub trash: TrashSettings,
+    #[serde(default)]
+    pub store: StoreSettings,
+    #[serde(default)]
+    pub auth: AuthSettings
+}
+
+/// `[limits]` table of quotes.toml, in characters.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct QuoteLimits {
+    #[serde(default = "default_max_author_length")]
+    pub max_author_length: usize,
+    #[serde(default = "default_max_quote_length")]
+    pub max_quote_length: usize
+}
+
+fn default_max_author_length() -> usize {
+    200
+}
+
+fn default_max_quote_length() -> usize {
+    2000
+}
+
+impl Default for QuoteLimits {
+    fn default() -> Self {
+        Self {
+            max_author_length: default_max_author_length(),
+            max_quote_length: default_max_quote_length()
+        }
+    }
+}
+
+/// `[trash]` table of quotes.toml. Times are in seconds.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TrashSettings {
+    /// How long removed quotes stay restorable before being purged.
+    #[serde(default = "default_retention")]
+    pub retention: u64,
+    #[serde(default = "default_purge_interval")]
+    pub purge_interval: u64,
+    /// How long the token handed out by `/19/reset` can be used to confirm.
+    #[serde(default = "default_reset_confirmation_ttl")]
+    pub reset_confirmation_ttl: u64
+}
+
+fn default_retention() -> u64 {
+    30 * 24 * 3600
+}
+
+fn default_purge_interval() -> u64 {
+    3600
+}
+
+fn default_reset_confirmation_ttl() -> u64 {
+    60
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        Self {
+            retention: default_retention(),
+            purge_interval: default_purge_interval(),
+            reset_confirmation_ttl: default_reset_confirmation_ttl()
+        }
+    }
+}
+
+/// `[store]` table of quotes.toml.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct StoreSettings {
+    #[serde(default)]
+    pub backend: StoreBackend,
+    /// Database file of the `sqlite` backend, created when missing.
+    #[serde(default = "default_sqlite_path")]
+    pub sqlite_path: PathBuf
+}
+
+/// Where quotes are kept. `sqlite` and `memory` also keep the gift token
+/// revocations, so Postgres isn't needed at all, though it is still
+/// provisioned unless built without the `shared-db` feature. `sqlite` search
+/// has no stemming or ranking, and `memory` forgets everything on restart.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum StoreBackend {
+    #[default]
+    Postgres,
+    Sqlite,
+    Memory
+}
+
+fn default_sqlite_path() -> PathBuf {
+    PathBuf::from("quotes.db")
+}
+
+impl Default for StoreSettings {
+    fn default() -> Self {
+        Self {
+            backend: StoreBackend::default(),
+            sqlite_path: default_sqlite_path()
+        }
+    }
+}
+
+/// `[auth]` table of quotes.toml. The defaults keep drafting open to anyone,
+/// as `/19` always allowed, and resetting to admins. Changing other quotes
+/// always needs a token.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct AuthSettings {
+    /// Lets callers without a token draft quotes, which then have no owner
+    /// and can only be changed by admins.
+    #[serde(default = "default_anonymous_drafts")]
+    pub anonymous_drafts: bool,
+    /// Keeps `/19/reset` to admins. Turning it off lets anyone trash every
+    /// quote.
+    #[serde(default = "default_admin_reset")]
+    pub admin_reset: bool
+}
+
+fn default_anonymous_drafts() -> bool {
+    true
+}
+
+fn default_admin_reset() -> bool {
+    true
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        Self {
+            anonymous_drafts: default_anonymous_drafts(),
+            admin_reset: default_admin_reset()
+        }
+    }
+}
+
+impl QuotesConfig {
+    /// Falls back to the defaults when the file doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                println!("No quotes config at {}, using defaults", path.display());
+                return Ok(Self::default())
+            },
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e))
+        };
+
+        let config: Self = toml::from_str(&contents)
+            .map_err(|e| format!("Invalid quotes config in {}: {}", path.display(), e))?;
+
+        if config.limits.max_author_length == 0 || config.limits.max_quote_length == 0 {
+            return Err(format!("Quote length limits in {} must be positive", path.display()))
+        }
+
+        if config.trash.purge_interval == 0 {
+            return Err(format!("purge-interval in {} must be positive", path.display()))
+        }
+
+        if config.trash.retention > MAX_RETENTION {
+            return Err(format!("retention in {} can't exceed {} seconds", path.display(), MAX_RETENTION))
+        }
+
+        if config.trash.reset_confirmation_ttl > MAX_RESET_CONFIRMATION_TTL {
+            return Err(format!("reset-confirmation-ttl in {} can't exceed {} seconds", path.display(), MAX_RESET_CONFIRMATION_TTL))
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_trash_settings() {
+        let path = std::env::temp_dir().join(format!("quotes_{}.toml", std::process::id()));
+
+        fs::write(&path, format!("[trash]\nretention = {}\n", MAX_RETENTION)).unwrap();
+        assert_eq!(QuotesConfig::load(&path).unwrap().trash.retention, MAX_RETENTION);
+
+        fs::write(&path, format!("[trash]\nretention = {}\n", i64::MAX)).unwrap();
+        assert!(QuotesConfig::load(&path).unwrap_err().starts_with("retention"));
+
+        fs::write(&path, format!("[trash]\nreset-confirmation-ttl = {}\n", i64::MAX)).unwrap();
+        assert!(QuotesConfig::load(&path).unwrap_err().starts_with("reset-confirmation-ttl"));
+
+        fs::write(&path, "[trash]\npurge-interval = 0\n").unwrap();
+        assert!(QuotesConfig::load(&path).unwrap_err().starts_with("purge-interval"));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keeps_resets_to_admins_by_default() {
+        let path = std::env::temp_dir().join(format!("quotes_auth_{}.toml", std::process::id()));
+
+        fs::write(&path, "[auth]\nanonymous-drafts = false\n").unwrap();
+        assert!(QuotesConfig::load(&path).unwrap().auth.admin_reset);
+
+        fs::write(&path, "[auth]\nadmin-reset = false\n").unwrap();
+        assert!(!QuotesConfig::load(&path).unwrap().auth.admin_reset);
+
+        fs::remove_file(&path).unwrap();
+
+        assert!(AuthSettings::default().admin_reset);
+    }
+}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use ring::digest::{digest, SHA256};
+
+/// Where in the quote timeline the quote of the day sits, derived from the
+/// date alone so every instance agrees on it.
+pub fn daily_fraction(date: &NaiveDate) -> f64 {
+    let hash = digest(&SHA256, date.format("%Y-%m-%d").to_string().as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash.as_ref()[..8]);
+
+    // The top 53 bits fill an f64 mantissa exactly
+    (u64::from_be_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+pub fn start_of_day(date: &NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0)
+        .expect("Midnight is a valid time")
+        .and_utc()
+}
+
+/// How long the quote of `date` stays the quote of the day.
+pub fn seconds_until_next_day(date: &NaiveDate, now: &DateTime<Utc>) -> i64 {
+    (start_of_day(date) + TimeDelta::days(1) - *now).num_seconds().max(0)
+}
@@ -0,0 +1,116 @@
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::Serialize;
+
+use super::validation::FieldError;
+
+/// Errors of the /19 endpoints, rendered as
+/// `{"error": {"code", "message", "fields"}}` with a matching status.
+#[derive(Debug)]
+pub enum QuoteError {
+    Status(StatusCode),
+    InvalidBody(String),
+    Validation(Vec<FieldError>),
+    Database(sqlx::Error)
+}
+
+#[derive(Serialize, Debug)]
+struct ErrorBody {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<FieldError>
+}
+
+#[derive(Serialize, Debug)]
+struct ErrorEnvelope {
+    error: ErrorBody
+}
+
+impl From<StatusCode> for QuoteError {
+    fn from(status: StatusCode) -> Self {
+        QuoteError::Status(status)
+    }
+}
+
+impl From<sqlx::Error> for QuoteError {
+    fn from(error: sqlx::Error) -> Self {
+        QuoteError::Database(error)
+    }
+}
+
+impl From<Vec<FieldError>> for QuoteError {
+    fn from(errors: Vec<FieldError>) -> Self {
+        QuoteError::Validation(errors)
+    }
+}
+
+impl QuoteError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            QuoteError::Status(status) => *status,
+            QuoteError::InvalidBody(_) => StatusCode::BAD_REQUEST,
+            QuoteError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            QuoteError::Database(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+            QuoteError::Database(sqlx::Error::PoolTimedOut) => StatusCode::SERVICE_UNAVAILABLE,
+            QuoteError::Database(sqlx::Error::Database(e))
+                if e.is_unique_violation() || e.is_foreign_key_violation() => StatusCode::CONFLICT,
+            QuoteError::Database(sqlx::Error::Database(e)) if e.is_check_violation() => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            },
+            QuoteError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+
+    fn code(&self, status: StatusCode) -> &'static str {
+        match (self, status) {
+            (QuoteError::InvalidBody(_), _) => "invalid_body",
+            (QuoteError::Validation(_), _) => "validation_failed",
+            (_, StatusCode::UNAUTHORIZED) => "unauthorized",
+            (_, StatusCode::FORBIDDEN) => "forbidden",
+            (_, StatusCode::NOT_FOUND) => "not_found",
+            (_, StatusCode::CONFLICT) => "conflict",
+            (_, StatusCode::PRECONDITION_FAILED) => "precondition_failed",
+            (_, StatusCode::SERVICE_UNAVAILABLE) => "unavailable",
+            (_, status) if status.is_client_error() => "bad_request",
+            _ => "internal_error"
+        }
+    }
+
+    /// Database details stay in the logs.
+    pub fn message(&self, status: StatusCode) -> String {
+        match self {
+            QuoteError::InvalidBody(message) => message.clone(),
+            QuoteError::Validation(_) => "Invalid quote".to_string(),
+            QuoteError::Database(sqlx::Error::Database(e)) if status == StatusCode::CONFLICT => {
+                format!("Conflicts with an existing quote ({})", e.constraint().unwrap_or("unique"))
+            },
+            _ => status.canonical_reason().unwrap_or("Error").to_string()
+        }
+    }
+}
+
+impl IntoResponse for QuoteError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let code = self.code(status);
+
+        if let QuoteError::Database(e) = &self {
+            println!("Quote database error: {}", e);
+        }
+
+        let message = self.message(status);
+
+        let fields = match self {
+            QuoteError::Validation(fields) => fields,
+            _ => Vec::new()
+        };
+
+        (status, Json(ErrorEnvelope {
+            error: ErrorBody {
+                code,
+                message,
+                fields
+            }
+        })).into_response()
+    }
+}
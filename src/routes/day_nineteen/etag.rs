@@ -0,0 +1,120 @@
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use uuid::Uuid;
+
+use super::Quote;
+
+/// A quote's ETag is `"<id>:<version>"`, so it changes with every update.
+pub fn etag(quote: &Quote) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let value = format!("\"{}:{}\"", quote.id, quote.version);
+
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        headers.insert(header::ETAG, value);
+    }
+
+    headers
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IfMatch {
+    Any,
+    Version(i32)
+}
+
+/// Reads the version expected by `If-Match`. Tags that can't match the quote
+/// (other ids, weak tags, garbage) fail the precondition right away, as
+/// strong comparison requires.
+pub fn if_match(headers: &HeaderMap, id: &Uuid) -> Result<Option<IfMatch>, StatusCode> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None)
+    };
+
+    let value = value.to_str().map_err(|e| {
+        println!("Invalid If-Match header: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    if value.trim() == "*" {
+        return Ok(Some(IfMatch::Any))
+    }
+
+    value.split(',')
+        .filter_map(|tag| {
+            let (tag_id, version) = tag.trim()
+                .strip_prefix('"')?
+                .strip_suffix('"')?
+                .split_once(':')?;
+
+            (tag_id.parse::<Uuid>().ok()? == *id)
+                .then(|| version.parse::<i32>().ok())
+                .flatten()
+        })
+        .next()
+        .map(|version| Some(IfMatch::Version(version)))
+        .ok_or_else(|| {
+            println!("If-Match {} can't match quote {}", value, id);
+            StatusCode::PRECONDITION_FAILED
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_if_match(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MATCH, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn reads_the_expected_version() {
+        let id = Uuid::new_v4();
+
+        assert_eq!(if_match(&HeaderMap::new(), &id), Ok(None));
+        assert_eq!(if_match(&with_if_match(" * "), &id), Ok(Some(IfMatch::Any)));
+        assert_eq!(if_match(&with_if_match(&format!("\"{}:3\"", id)), &id), Ok(Some(IfMatch::Version(3))));
+    }
+
+    #[test]
+    fn picks_this_quote_out_of_a_list() {
+        let id = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let value = format!("W/\"{id}:7\", \"{other}:3\",\"{id}:5\"", id = id, other = other);
+
+        assert_eq!(if_match(&with_if_match(&value), &id), Ok(Some(IfMatch::Version(5))));
+        assert_eq!(if_match(&with_if_match(&value), &other), Ok(Some(IfMatch::Version(3))));
+    }
+
+    #[test]
+    fn fails_tags_that_cant_match() {
+        let id = Uuid::new_v4();
+        let failed = |value: &str| if_match(&with_if_match(value), &id);
+
+        assert_eq!(failed(&format!("W/\"{}:3\"", id)), Err(StatusCode::PRECONDITION_FAILED));
+        assert_eq!(failed(&format!("\"{}:3\"", Uuid::new_v4())), Err(StatusCode::PRECONDITION_FAILED));
+        assert_eq!(failed(&format!("{}:3", id)), Err(StatusCode::PRECONDITION_FAILED));
+        assert_eq!(failed(&format!("\"{}:three\"", id)), Err(StatusCode::PRECONDITION_FAILED));
+        assert_eq!(failed("\"*\""), Err(StatusCode::PRECONDITION_FAILED));
+    }
+
+    #[test]
+    fn tags_change_with_the_version() {
+        let quote = Quote {
+            id: Uuid::new_v4(),
+            author: "Santa".to_string(),
+            quote: "Ho ho ho".to_string(),
+            created_at: chrono::Utc::now(),
+            version: 2,
+            deleted_at: None,
+            owner: None,
+            changed_by: None
+        };
+
+        let headers = etag(&quote);
+        let tag = headers.get(header::ETAG).unwrap().to_str().unwrap();
+
+        assert_eq!(tag, format!("\"{}:2\"", quote.id));
+        assert_eq!(if_match(&with_if_match(tag), &quote.id), Ok(Some(IfMatch::Version(2))));
+    }
+}
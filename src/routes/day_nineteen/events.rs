@@ -0,0 +1,182 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    http::StatusCode,
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse}
+};
+use futures_util::Stream;
+use serde::Deserialize;
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::{sync::broadcast::{self, error::RecvError}, time::{sleep, Duration}};
+use uuid::Uuid;
+
+use super::{error::QuoteError, quote_repository::QuoteRepository, Quote};
+use crate::AppState;
+
+/// Channel notified by the `quotes_notify_change` trigger.
+pub const QUOTE_CHANGES_CHANNEL: &str = "quote_changes";
+
+/// How many events a slow subscriber can fall behind before missing some.
+pub const QUOTE_EVENTS_CAPACITY: usize = 256;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteEventKind {
+    Created,
+    Updated,
+    Deleted
+}
+
+/// `quote` is left out by the trigger when it wouldn't fit in a notification.
+#[derive(Deserialize, Debug)]
+struct QuoteChange {
+    event: QuoteEventKind,
+    id: Uuid,
+    quote: Option<Quote>
+}
+
+/// `quote` is `None` when it was too large to be sent along and was deleted
+/// outright before it could be loaded.
+#[derive(Debug, Clone)]
+pub struct QuoteEvent {
+    pub kind: QuoteEventKind,
+    pub id: Uuid,
+    pub quote: Option<Quote>
+}
+
+impl QuoteEventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            QuoteEventKind::Created => "created",
+            QuoteEventKind::Updated => "updated",
+            QuoteEventKind::Deleted => "deleted"
+        }
+    }
+}
+
+/// Relays quote change notifications to `sender` for as long as the service
+/// runs. Notifications carry the quote as changed, except for quotes too large
+/// to fit, which are loaded when the notification arrives. Lost connections
+/// are re-established by the listener; changes made in the meantime are
+/// missed.
+pub async fn relay_quote_changes(
+    pool: PgPool,
+    quote_repository: Arc<dyn QuoteRepository>,
+    sender: broadcast::Sender<QuoteEvent>
+) {
+    loop {
+        let mut listener = match PgListener::connect_with(&pool).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                println!("Error connecting quote change listener: {}", e);
+                sleep(Duration::from_secs(5)).await;
+                continue
+            }
+        };
+
+        if let Err(e) = listener.listen(QUOTE_CHANGES_CHANNEL).await {
+            println!("Error listening for quote changes: {}", e);
+            sleep(Duration::from_secs(5)).await;
+            continue
+        }
+
+        loop {
+            let notification = match listener.recv().await {
+                Ok(notification) => notification,
+                Err(e) => {
+                    println!("Error receiving quote change: {}", e);
+                    break
+                }
+            };
+
+            let change = match serde_json::from_str::<QuoteChange>(notification.payload()) {
+                Ok(change) => change,
+                Err(e) => {
+                    println!("Invalid quote change {:?}: {}", notification.payload(), e);
+                    continue
+                }
+            };
+
+            let quote = match change.quote {
+                Some(quote) => Some(quote),
+                None => match quote_repository.find_quote(&change.id).await {
+                    Ok(quote) => quote,
+                    Err(e) => {
+                        println!("Error loading changed quote {}: {}", change.id, e);
+                        continue
+                    }
+                }
+            };
+
+            // Fails when nobody is subscribed, which is fine
+            let _ = sender.send(QuoteEvent { kind: change.event, id: change.id, quote });
+        }
+    }
+}
+
+/// Server-sent events named after the change, with the quote as data, or
+/// only its id when the quote is gone.
+#[axum::debug_handler]
+pub async fn events(
+    State(state): State<Arc<AppState>>
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, QuoteError> {
+    let mut receiver = state.quote_events
+        .as_ref()
+        .ok_or(StatusCode::NOT_IMPLEMENTED)?
+        .subscribe();
+
+    let stream = async_stream::stream! {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => match quote_event_data(&event) {
+                    Ok(sse_event) => yield Ok(sse_event),
+                    Err(e) => println!("Error encoding quote event: {}", e)
+                },
+                Err(RecvError::Lagged(missed)) => println!("Quote event subscriber missed {} events", missed),
+                Err(RecvError::Closed) => break
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+fn quote_event_data(event: &QuoteEvent) -> Result<Event, axum::Error> {
+    let sse_event = Event::default().event(event.kind.as_str());
+
+    match &event.quote {
+        Some(quote) => sse_event.json_data(quote),
+        None => sse_event.json_data(serde_json::json!({ "id": event.id }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// As sent by `notify_quote_change`, `row_to_json` columns included.
+    const DELETED: &str = r#"{"event" : "deleted", "id" : "4a1f4bd2-0d8e-4a49-9e0c-3d8a6c1f2b7e", "quote" : {"id":"4a1f4bd2-0d8e-4a49-9e0c-3d8a6c1f2b7e","author":"Santa","quote":"Ho ho ho","created_at":"2025-01-15T12:00:00.123456+00:00","version":3,"deleted_at":"2025-01-16T08:30:00+00:00","owner":"santa","changed_by":"elf","pick_key":0.42}}"#;
+
+    #[test]
+    fn reads_the_quote_from_notifications() {
+        let change = serde_json::from_str::<QuoteChange>(DELETED).unwrap();
+        let quote = change.quote.unwrap();
+
+        assert_eq!(change.event, QuoteEventKind::Deleted);
+        assert_eq!(quote.id, change.id);
+        assert_eq!((quote.quote.as_str(), quote.version, quote.changed_by.as_deref()), ("Ho ho ho", 3, Some("elf")));
+        assert_eq!(quote.deleted_at.unwrap().to_rfc3339(), "2025-01-16T08:30:00+00:00");
+    }
+
+    #[test]
+    fn reads_notifications_without_the_quote() {
+        let change = serde_json::from_str::<QuoteChange>(
+            r#"{"event" : "updated", "id" : "4a1f4bd2-0d8e-4a49-9e0c-3d8a6c1f2b7e"}"#
+        ).unwrap();
+
+        assert_eq!(change.event, QuoteEventKind::Updated);
+        assert!(change.quote.is_none());
+        assert!(serde_json::from_str::<QuoteChange>(r#"{"event" : "trashed", "id" : "4a1f4bd2-0d8e-4a49-9e0c-3d8a6c1f2b7e"}"#).is_err());
+    }
+}
@@ -0,0 +1,712 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap, HashSet},
+    error::Error as StdError,
+    fmt,
+    sync::Arc
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::{stream::{self, BoxStream}, StreamExt};
+use sqlx::{error::{DatabaseError, ErrorKind}, Error};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::{
+    collections::{
+        Collection, CollectionForCreation, CollectionForUpdate, CollectionOutcome,
+        CollectionSummary, CollectionWithQuotes
+    },
+    quote_repository::{QuoteRepository, UpdateOutcome},
+    search::{QuoteSearch, SearchHit, SearchResults, SearchTerms},
+    transfer::{ImportError, QuoteForImport},
+    Quote, QuoteForCreation, QuoteForUpdate, QuoteHistory, QuoteVersion
+};
+
+/// Quotes kept in memory, trashed ones included, along with everything the
+/// database stores keep about them. A single lock makes every operation
+/// atomic, like the transactions of the database stores. Meant for local runs
+/// and demos: everything is lost on restart.
+#[derive(Default)]
+pub struct MemoryQuoteRepository {
+    store: Arc<RwLock<MemoryStore>>
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    quotes: HashMap<Uuid, Quote>,
+    /// Past versions of each quote, oldest first.
+    versions: HashMap<Uuid, Vec<QuoteVersion>>,
+    tags: HashMap<Uuid, BTreeSet<String>>,
+    collections: HashMap<Uuid, MemoryCollection>
+}
+
+struct MemoryCollection {
+    collection: Collection,
+    /// Trashed quotes stay in the collection until purged.
+    quotes: Vec<Uuid>
+}
+
+impl MemoryStore {
+    fn live_quote(&self, id: &Uuid) -> Option<&Quote> {
+        self.quotes.get(id).filter(|quote| quote.deleted_at.is_none())
+    }
+
+    /// Live quotes oldest first, like the databases order them.
+    fn live_quotes(&self) -> Vec<&Quote> {
+        let mut quotes = self.quotes.values()
+            .filter(|quote| quote.deleted_at.is_none())
+            .collect::<Vec<_>>();
+
+        quotes.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+
+        quotes
+    }
+
+    /// Copies the current quote into its history before it gets overwritten
+    /// or trashed.
+    fn archive(&mut self, quote: &Quote, superseded_by: &str) {
+        self.versions.entry(quote.id).or_default().push(QuoteVersion {
+            quote_id: quote.id,
+            version: quote.version,
+            author: quote.author.clone(),
+            quote: quote.quote.clone(),
+            created_at: quote.created_at,
+            superseded_at: Utc::now(),
+            superseded_by: superseded_by.to_string(),
+            changed_by: quote.changed_by.clone()
+        });
+    }
+
+    /// Archives a live quote and moves it to the trash as a new version.
+    fn trash(
+        &mut self,
+        quote: Quote,
+        deleted_at: DateTime<Utc>,
+        changed_by: Option<&str>,
+        superseded_by: &str
+    ) -> Quote {
+        self.archive(&quote, superseded_by);
+
+        let quote = Quote {
+            version: quote.version + 1,
+            deleted_at: Some(deleted_at),
+            changed_by: changed_by.map(str::to_string),
+            ..quote
+        };
+
+        self.quotes.insert(quote.id, quote.clone());
+
+        quote
+    }
+
+    fn quote_tags(&self, id: &Uuid) -> Vec<String> {
+        self.tags.get(id)
+            .map(|tags| tags.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn with_quotes(&self, collection: &MemoryCollection) -> CollectionWithQuotes {
+        CollectionWithQuotes {
+            collection: collection.collection.clone(),
+            quotes: collection.quotes.iter()
+                .filter_map(|id| self.live_quote(id).cloned())
+                .collect()
+        }
+    }
+
+    fn name_taken(&self, name: &str, except: Option<&Uuid>) -> bool {
+        self.collections.values().any(|collection| {
+            collection.collection.name == name && Some(&collection.collection.id) != except
+        })
+    }
+
+    fn unknown_quotes(&self, quotes: &[Uuid]) -> Vec<Uuid> {
+        quotes.iter()
+            .filter(|id| self.live_quote(id).is_none())
+            .copied()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl QuoteRepository for MemoryQuoteRepository {
+    async fn create_quote(
+        &self,
+        quote_for_creation: QuoteForCreation,
+        owner: Option<&str>
+    ) -> Result<Quote, Error> {
+        let quote = Quote {
+            id: Uuid::new_v4(),
+            author: quote_for_creation.author,
+            quote: quote_for_creation.quote,
+            created_at: Utc::now(),
+            version: 1,
+            deleted_at: None,
+            owner: owner.map(str::to_string),
+            changed_by: owner.map(str::to_string)
+        };
+
+        self.store.write().await.quotes.insert(quote.id, quote.clone());
+
+        Ok(quote)
+    }
+
+    async fn get_quote(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Quote>, Error> {
+        Ok(self.store.read().await.live_quote(id).cloned())
+    }
+
+    async fn find_quote(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Quote>, Error> {
+        Ok(self.store.read().await.quotes.get(id).cloned())
+    }
+
+    async fn delete_quote(
+        &self,
+        id: &Uuid,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error> {
+        let mut store = self.store.write().await;
+
+        let Some(current) = store.live_quote(id).cloned() else {
+            return Ok(None)
+        };
+
+        Ok(Some(store.trash(current, Utc::now(), Some(changed_by), "delete")))
+    }
+
+    async fn restore_quote(
+        &self,
+        id: &Uuid,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error> {
+        let mut store = self.store.write().await;
+
+        Ok(store.quotes.get_mut(id)
+            .filter(|quote| quote.deleted_at.is_some())
+            .map(|quote| {
+                quote.deleted_at = None;
+                quote.changed_by = Some(changed_by.to_string());
+                quote.clone()
+            }))
+    }
+
+    async fn list_trash(&self) -> Result<Vec<Quote>, Error> {
+        let mut trash = self.store.read().await.quotes
+            .values()
+            .filter(|quote| quote.deleted_at.is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        trash.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(a.id.cmp(&b.id)));
+
+        Ok(trash)
+    }
+
+    async fn update_quote(
+        &self,
+        id: &Uuid,
+        quote_for_update: &QuoteForUpdate,
+        expected_version: Option<i32>,
+        changed_by: &str
+    ) -> Result<UpdateOutcome, Error> {
+        let mut store = self.store.write().await;
+
+        let Some(current) = store.live_quote(id).cloned() else {
+            return Ok(UpdateOutcome::NotFound)
+        };
+
+        if expected_version.is_some_and(|version| version != current.version) {
+            return Ok(UpdateOutcome::Conflict(current.version))
+        }
+
+        store.archive(&current, "update");
+
+        let quote = Quote {
+            author: quote_for_update.author.clone(),
+            quote: quote_for_update.quote.clone(),
+            version: current.version + 1,
+            changed_by: Some(changed_by.to_string()),
+            ..current
+        };
+
+        store.quotes.insert(quote.id, quote.clone());
+
+        Ok(UpdateOutcome::Updated(quote))
+    }
+
+    async fn clean_db(&self, changed_by: Option<&str>) -> Result<u64, Error> {
+        let now = Utc::now();
+        let mut store = self.store.write().await;
+
+        let live = store.live_quotes()
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for quote in &live {
+            store.trash(quote.clone(), now, changed_by, "reset");
+        }
+
+        Ok(live.len() as u64)
+    }
+
+    async fn purge_deleted(
+        &self,
+        deleted_before: &DateTime<Utc>
+    ) -> Result<u64, Error> {
+        let mut store = self.store.write().await;
+
+        let purged = store.quotes.values()
+            .filter(|quote| quote.deleted_at.is_some_and(|deleted_at| deleted_at < *deleted_before))
+            .map(|quote| quote.id)
+            .collect::<HashSet<_>>();
+
+        for id in &purged {
+            store.quotes.remove(id);
+            store.tags.remove(id);
+        }
+
+        for collection in store.collections.values_mut() {
+            collection.quotes.retain(|id| !purged.contains(id));
+        }
+
+        Ok(purged.len() as u64)
+    }
+
+    async fn get_history(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<QuoteHistory>, Error> {
+        let store = self.store.read().await;
+        let current = store.quotes.get(id).cloned();
+        let versions = store.versions.get(id).cloned().unwrap_or_default();
+
+        if current.is_none() && versions.is_empty() {
+            return Ok(None)
+        }
+
+        Ok(Some(QuoteHistory {
+            current,
+            versions
+        }))
+    }
+
+    async fn revert_quote(
+        &self,
+        id: &Uuid,
+        version: i32,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error> {
+        let mut store = self.store.write().await;
+
+        let Some(past) = store.versions.get(id)
+            .and_then(|versions| versions.iter().find(|past| past.version == version))
+            .cloned() else {
+            return Ok(None)
+        };
+
+        let quote = match store.quotes.get(id).cloned() {
+            Some(current) => {
+                store.archive(&current, "revert");
+
+                Quote {
+                    author: past.author,
+                    quote: past.quote,
+                    version: current.version + 1,
+                    deleted_at: None,
+                    changed_by: Some(changed_by.to_string()),
+                    ..current
+                }
+            },
+            None => Quote {
+                id: *id,
+                author: past.author,
+                quote: past.quote,
+                created_at: past.created_at,
+                version: store.versions[id].iter().map(|past| past.version).max().unwrap_or(version) + 1,
+                deleted_at: None,
+                owner: None,
+                changed_by: Some(changed_by.to_string())
+            }
+        };
+
+        store.quotes.insert(quote.id, quote.clone());
+
+        Ok(Some(quote))
+    }
+
+    /// A snapshot taken when the stream is first polled.
+    fn export_quotes(&self) -> BoxStream<'static, Result<Quote, Error>> {
+        let store = self.store.clone();
+
+        stream::once(async move {
+            let quotes = store.read().await
+                .live_quotes()
+                .into_iter()
+                .cloned()
+                .map(Ok)
+                .collect::<Vec<_>>();
+
+            stream::iter(quotes)
+        })
+        .flatten()
+        .boxed()
+    }
+
+    async fn import_quotes(
+        &self,
+        quotes: &[(u64, QuoteForImport)],
+        owner: &str,
+        dry_run: bool
+    ) -> Result<Vec<ImportError>, Error> {
+        let mut store = self.store.write().await;
+        let mut imported = Vec::new();
+        let mut ids = HashSet::new();
+        let mut errors = Vec::new();
+
+        for (line, quote) in quotes {
+            let id = quote.id.unwrap_or_else(Uuid::new_v4);
+
+            if store.quotes.contains_key(&id) || store.versions.contains_key(&id) || !ids.insert(id) {
+                errors.push(ImportError::new(*line, format!("Quote {} already exists or was purged", id)));
+                continue
+            }
+
+            imported.push(Quote {
+                id,
+                author: quote.author.clone(),
+                quote: quote.quote.clone(),
+                created_at: quote.created_at.unwrap_or_else(Utc::now),
+                version: 1,
+                deleted_at: None,
+                owner: Some(owner.to_string()),
+                changed_by: Some(owner.to_string())
+            });
+        }
+
+        if !dry_run && errors.is_empty() {
+            store.quotes.extend(imported.into_iter().map(|quote| (quote.id, quote)));
+        }
+
+        Ok(errors)
+    }
+
+    async fn pick_quote(
+        &self,
+        fraction: f64,
+        author: Option<&str>,
+        created_before: Option<&DateTime<Utc>>
+    ) -> Result<Option<Quote>, Error> {
+        let store = self.store.read().await;
+        let author = author.map(str::to_lowercase);
+
+        let quotes = store.live_quotes()
+            .into_iter()
+            .filter(|quote| author.as_ref().is_none_or(|author| quote.author.to_lowercase() == *author))
+            .filter(|quote| created_before.is_none_or(|created_before| quote.created_at < *created_before))
+            .collect::<Vec<_>>();
+
+        let position = (fraction * quotes.len() as f64) as usize;
+
+        Ok(quotes.get(position).map(|quote| (*quote).clone()))
+    }
+
+    async fn tag_quote(
+        &self,
+        id: &Uuid,
+        tag: &str
+    ) -> Result<Option<Vec<String>>, Error> {
+        let mut store = self.store.write().await;
+
+        if store.live_quote(id).is_none() {
+            return Ok(None)
+        }
+
+        store.tags.entry(*id).or_default().insert(tag.to_string());
+
+        Ok(Some(store.quote_tags(id)))
+    }
+
+    /// Tags only exist through the quotes using them, so there is nothing else
+    /// to drop.
+    async fn untag_quote(
+        &self,
+        id: &Uuid,
+        tag: &str
+    ) -> Result<Option<Vec<String>>, Error> {
+        let mut store = self.store.write().await;
+
+        if store.live_quote(id).is_none() {
+            return Ok(None)
+        }
+
+        if let Some(tags) = store.tags.get_mut(id) {
+            tags.remove(tag);
+        }
+
+        Ok(Some(store.quote_tags(id)))
+    }
+
+    async fn get_tags(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Vec<String>>, Error> {
+        let store = self.store.read().await;
+
+        Ok(store.live_quote(id).map(|_| store.quote_tags(id)))
+    }
+
+    async fn list_tagged(
+        &self,
+        tag: &str
+    ) -> Result<Vec<Quote>, Error> {
+        let store = self.store.read().await;
+
+        Ok(store.live_quotes()
+            .into_iter()
+            .filter(|quote| store.tags.get(&quote.id).is_some_and(|tags| tags.contains(tag)))
+            .cloned()
+            .collect())
+    }
+
+    async fn create_collection(
+        &self,
+        collection_for_creation: &CollectionForCreation,
+        owner: &str
+    ) -> Result<CollectionOutcome, Error> {
+        let mut store = self.store.write().await;
+
+        if store.name_taken(&collection_for_creation.name, None) {
+            return Err(UniqueViolation::collection_name())
+        }
+
+        let unknown = store.unknown_quotes(&collection_for_creation.quotes);
+        if !unknown.is_empty() {
+            return Ok(CollectionOutcome::UnknownQuotes(unknown))
+        }
+
+        let collection = MemoryCollection {
+            collection: Collection {
+                id: Uuid::new_v4(),
+                name: collection_for_creation.name.clone(),
+                created_at: Utc::now(),
+                owner: Some(owner.to_string())
+            },
+            quotes: collection_for_creation.quotes.clone()
+        };
+
+        let saved = store.with_quotes(&collection);
+        store.collections.insert(collection.collection.id, collection);
+
+        Ok(CollectionOutcome::Saved(saved))
+    }
+
+    async fn list_collections(&self) -> Result<Vec<CollectionSummary>, Error> {
+        let store = self.store.read().await;
+
+        let mut collections = store.collections.values()
+            .map(|collection| CollectionSummary {
+                collection: collection.collection.clone(),
+                size: collection.quotes.len() as i64
+            })
+            .collect::<Vec<_>>();
+
+        collections.sort_by(|a, b| a.collection.name.cmp(&b.collection.name));
+
+        Ok(collections)
+    }
+
+    async fn get_collection(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<CollectionWithQuotes>, Error> {
+        let store = self.store.read().await;
+
+        Ok(store.collections.get(id).map(|collection| store.with_quotes(collection)))
+    }
+
+    async fn update_collection(
+        &self,
+        id: &Uuid,
+        collection_for_update: &CollectionForUpdate
+    ) -> Result<CollectionOutcome, Error> {
+        let mut store = self.store.write().await;
+
+        if !store.collections.contains_key(id) {
+            return Ok(CollectionOutcome::NotFound)
+        }
+
+        if let Some(name) = &collection_for_update.name {
+            if store.name_taken(name, Some(id)) {
+                return Err(UniqueViolation::collection_name())
+            }
+        }
+
+        if let Some(quotes) = &collection_for_update.quotes {
+            let unknown = store.unknown_quotes(quotes);
+            if !unknown.is_empty() {
+                return Ok(CollectionOutcome::UnknownQuotes(unknown))
+            }
+        }
+
+        let Some(collection) = store.collections.get_mut(id) else {
+            return Ok(CollectionOutcome::NotFound)
+        };
+
+        if let Some(name) = &collection_for_update.name {
+            collection.collection.name = name.clone();
+        }
+
+        if let Some(quotes) = &collection_for_update.quotes {
+            collection.quotes = quotes.clone();
+        }
+
+        Ok(CollectionOutcome::Saved(store.with_quotes(&store.collections[id])))
+    }
+
+    async fn delete_collection(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Collection>, Error> {
+        Ok(self.store.write().await.collections
+            .remove(id)
+            .map(|collection| collection.collection))
+    }
+
+    /// Matches come newest first, there's no ranking without full text search.
+    async fn search_quotes(
+        &self,
+        search: &QuoteSearch
+    ) -> Result<SearchResults, Error> {
+        let store = self.store.read().await;
+        let terms = search.q.as_deref().map(SearchTerms::parse);
+        let author = search.author.as_deref().map(str::to_lowercase);
+
+        let mut matches = store.live_quotes()
+            .into_iter()
+            .filter(|quote| terms.as_ref().is_none_or(|terms| terms.matches(&quote.quote)))
+            .filter(|quote| author.as_ref().is_none_or(|author| quote.author.to_lowercase() == *author))
+            .filter(|quote| search.since.is_none_or(|since| quote.created_at >= since))
+            .filter(|quote| search.until.is_none_or(|until| quote.created_at <= until))
+            .collect::<Vec<_>>();
+
+        matches.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.id.cmp(&b.id)));
+
+        let total = matches.len() as i64;
+
+        let results = matches.into_iter()
+            .skip(search.offset() as usize)
+            .take(search.per_page() as usize)
+            .map(|quote| SearchHit {
+                quote: quote.clone(),
+                rank: None,
+                highlight: terms.as_ref().map(|terms| terms.highlight(&quote.quote))
+            })
+            .collect();
+
+        let next_page = (search.offset() + search.per_page() < total)
+            .then(|| search.page() + 1);
+
+        Ok(SearchResults {
+            results,
+            page: search.page(),
+            per_page: search.per_page(),
+            total,
+            next_page
+        })
+    }
+}
+
+/// What the databases report for a name that's already taken, so it gets the
+/// same 409.
+#[derive(Debug)]
+struct UniqueViolation {
+    message: &'static str
+}
+
+impl UniqueViolation {
+    fn collection_name() -> Error {
+        Error::Database(Box::new(Self {
+            message: "A collection with this name already exists"
+        }))
+    }
+}
+
+impl fmt::Display for UniqueViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message)
+    }
+}
+
+impl StdError for UniqueViolation {}
+
+impl DatabaseError for UniqueViolation {
+    fn message(&self) -> &str {
+        self.message
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+
+    fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+        self
+    }
+
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::UniqueViolation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::MemoryQuoteRepository;
+    use crate::routes::day_nineteen::quote_repository::tests as suite;
+
+    #[tokio::test]
+    async fn keeps_history_and_reverts() {
+        suite::keeps_history_and_reverts(Arc::new(MemoryQuoteRepository::default())).await
+    }
+
+    #[tokio::test]
+    async fn purges_tags_and_collection_places() {
+        suite::purges_tags_and_collection_places(Arc::new(MemoryQuoteRepository::default())).await
+    }
+
+    #[tokio::test]
+    async fn refuses_taken_collection_names() {
+        suite::refuses_taken_collection_names(Arc::new(MemoryQuoteRepository::default())).await
+    }
+
+    #[tokio::test]
+    async fn rolls_back_imports_with_clashes() {
+        suite::rolls_back_imports_with_clashes(Arc::new(MemoryQuoteRepository::default())).await
+    }
+
+    #[tokio::test]
+    async fn searches_newest_first_with_highlights() {
+        suite::searches_newest_first_with_highlights(Arc::new(MemoryQuoteRepository::default())).await
+    }
+
+    #[tokio::test]
+    async fn picks_quotes_by_position() {
+        suite::picks_quotes_by_position(Arc::new(MemoryQuoteRepository::default())).await
+    }
+}
@@ -1,22 +1,125 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use axum::{extract::{Path, State}, http::StatusCode, Json};
+use axum::{
+    body::Body,
+    extract::{rejection::{JsonRejection, QueryRejection}, Path, Query, State},
+    http::{header::{ACCEPT, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE}, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json
+};
+use futures_util::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use rand::Rng;
 
-pub mod quote_controller;
+pub mod quote_repository;
+pub mod pg_quote_repository;
+pub mod memory_quote_repository;
+pub mod sqlite_quote_repository;
+pub mod search;
+pub mod etag;
+pub mod config;
+pub mod transfer;
+pub mod validation;
+pub mod error;
+pub mod daily;
+pub mod tags;
+pub mod collections;
+pub mod events;
+pub mod auth;
+pub mod ui;
 
-use crate::AppState;
+use search::{QuoteSearch, SearchResults};
+use etag::{etag, if_match, IfMatch};
+use quote_repository::UpdateOutcome;
+use transfer::{ImportError, ImportReport, TransferFormat};
+use config::QuoteLimits;
+use error::QuoteError;
+use validation::{validate_quote, FieldError};
+use daily::{daily_fraction, seconds_until_next_day, start_of_day};
+use auth::{authenticate, authenticate_draft, authorize_change, authorize_reset};
+use crate::{routes::admin::constant_time_eq, AppState};
 
-#[derive(FromRow, Debug, Serialize)]
+#[derive(FromRow, Debug, Clone, Serialize, Deserialize)]
 pub struct Quote {
     pub id: Uuid,
     pub author: String,
     pub quote: String,
     pub created_at: DateTime<Utc>,
-    pub version: i32
+    pub version: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// `None` for quotes drafted anonymously or before quotes had owners.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_by: Option<String>
+}
+
+/// A superseded version of a quote. `superseded_by` is the operation that
+/// replaced it: `update`, `revert`, `delete` or `reset`. `changed_by` is who
+/// made the version.
+#[derive(FromRow, Debug, Clone, Serialize)]
+pub struct QuoteVersion {
+    pub quote_id: Uuid,
+    pub version: i32,
+    pub author: String,
+    pub quote: String,
+    pub created_at: DateTime<Utc>,
+    pub superseded_at: DateTime<Utc>,
+    pub superseded_by: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_by: Option<String>
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuoteHistory {
+    pub current: Option<Quote>,
+    pub versions: Vec<QuoteVersion>
+}
+
+/// Token handed out by `/19/reset`, to be sent back within its lifetime to
+/// actually trash every quote.
+pub struct ResetConfirmation {
+    token: String,
+    expires_at: Instant
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ResetQuery {
+    pub confirm: Option<String>
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum ResetResponse {
+    Pending {
+        confirm: String,
+        expires_in: u64
+    },
+    Done {
+        trashed: u64
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub dry_run: bool
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RandomQuery {
+    pub author: Option<String>
+}
+
+/// `date` is a UTC date like `2024-12-25`, today by default.
+#[derive(Deserialize, Debug)]
+pub struct DailyQuery {
+    pub date: Option<NaiveDate>
 }
 
 #[derive(Deserialize, Debug)]
@@ -25,109 +128,383 @@ pub struct QuoteForCreation {
     pub quote: String
 }
 
+/// `version` is the version the update was based on, as an alternative to
+/// `If-Match`.
 #[derive(Deserialize, Debug)]
 pub struct QuoteForUpdate {
     pub author: String,
-    pub quote: String
+    pub quote: String,
+    pub version: Option<i32>
+}
+
+impl QuoteForCreation {
+    pub fn validate(self, limits: &QuoteLimits) -> Result<Self, Vec<FieldError>> {
+        let (author, quote) = validate_quote(&self.author, &self.quote, limits)?;
+
+        Ok(Self { author, quote })
+    }
+}
+
+impl QuoteForUpdate {
+    pub fn validate(self, limits: &QuoteLimits) -> Result<Self, Vec<FieldError>> {
+        let (author, quote) = validate_quote(&self.author, &self.quote, limits)?;
+
+        Ok(Self { author, quote, ..self })
+    }
 }
 
+/// The caller owns the new quote. Without a token, the quote has no owner,
+/// unless `anonymous-drafts` is turned off and a token is required.
 #[axum::debug_handler]
 pub async fn draft(
     State(state): State<Arc<AppState>>,
-    Json(body): Json<QuoteForCreation>
-) -> Result<(StatusCode, Json<Quote>), StatusCode> {
+    headers: HeaderMap,
+    body: Result<Json<QuoteForCreation>, JsonRejection>
+) -> Result<(StatusCode, HeaderMap, Json<Quote>), QuoteError> {
+    let caller = authenticate_draft(&state, &headers).await?;
 
-    match state.quote_controller
-        .create_quote(body)
-        .await 
-    {
-        Ok(quote) => Ok((StatusCode::CREATED, Json(quote))),
-        Err(e) => {
-            println!("Error inserting quote: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+    let Json(body) = body.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let body = body.validate(&state.quotes_config.limits)?;
+
+    let quote = state.quote_repository
+        .create_quote(body, caller.as_ref().map(|caller| caller.user.as_str()))
+        .await?;
+
+    Ok((StatusCode::CREATED, etag(&quote), Json(quote)))
 }
 
 #[axum::debug_handler]
 pub async fn cite(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>
-) -> Result<Json<Quote>, StatusCode> {
-    match state.quote_controller
+) -> Result<(HeaderMap, Json<Quote>), QuoteError> {
+    let quote = state.quote_repository
         .get_quote(&id)
-        .await
-    {
-        Ok(option) => {
-            match option {
-                Some(quote) => Ok(Json(quote)),
-                None => Err(StatusCode::NOT_FOUND)
-            }
-        },
-        Err(e) => {
-            println!("Error fetching quote: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok((etag(&quote), Json(quote)))
 }
 
+/// Owner or admin only, like every other change to a quote.
 #[axum::debug_handler]
 pub async fn remove(
     State(state): State<Arc<AppState>>,
-    Path(id): Path<Uuid>
-) -> Result<Json<Quote>, StatusCode> {
-    match state.quote_controller
-        .delete_quote(&id)
-        .await
-    {
-        Ok(option) => {
-            match option {
-                Some(quote) => Ok(Json(quote)),
-                None => Err(StatusCode::NOT_FOUND)
-            }
-        },
-        Err(e) => {
-            println!("Error deleting quote: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+    Path(id): Path<Uuid>,
+    headers: HeaderMap
+) -> Result<Json<Quote>, QuoteError> {
+    let caller = authorize_change(&state, &headers, &id).await?;
+
+    let quote = state.quote_repository
+        .delete_quote(&id, &caller.user)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(quote))
 }
 
+/// Honors `If-Match` or a `version` in the body, answering 412 when the quote
+/// was changed in the meantime.
 #[axum::debug_handler]
 pub async fn undo(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(body): Json<QuoteForUpdate>
-) -> Result<Json<Quote>, StatusCode> {
-    match state.quote_controller
-        .update_quote(&id, &body)
-        .await
-    {
-        Ok(option) => {
-            match option {
-                Some(quote) => Ok(Json(quote)),
-                None => Err(StatusCode::NOT_FOUND)
-            }
+    headers: HeaderMap,
+    body: Result<Json<QuoteForUpdate>, JsonRejection>
+) -> Result<(HeaderMap, Json<Quote>), QuoteError> {
+    let caller = authorize_change(&state, &headers, &id).await?;
+    let if_match = if_match(&headers, &id)?;
+
+    let Json(body) = body.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let body = body.validate(&state.quotes_config.limits)?;
+
+    let expected_version = match (&if_match, body.version) {
+        (Some(IfMatch::Version(version)), Some(body_version)) if *version != body_version => {
+            return Err(QuoteError::InvalidBody(
+                "If-Match and version disagree on the expected version".to_string()
+            ))
         },
-        Err(e) => {
-            println!("Error updating quote: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        (Some(IfMatch::Version(version)), _) => Some(*version),
+        (_, body_version) => body_version
+    };
+
+    match state.quote_repository
+        .update_quote(&id, &body, expected_version, &caller.user)
+        .await?
+    {
+        UpdateOutcome::Updated(quote) => Ok((etag(&quote), Json(quote))),
+        UpdateOutcome::NotFound if if_match.is_some() => Err(StatusCode::PRECONDITION_FAILED.into()),
+        UpdateOutcome::NotFound => Err(StatusCode::NOT_FOUND.into()),
+        UpdateOutcome::Conflict(current_version) => {
+            println!("Quote {} is at version {}, expected {:?}", id, current_version, expected_version);
+            Err(StatusCode::PRECONDITION_FAILED.into())
         }
     }
 }
 
+#[axum::debug_handler]
+pub async fn history(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>
+) -> Result<Json<QuoteHistory>, QuoteError> {
+    let history = state.quote_repository
+        .get_history(&id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(history))
+}
+
+#[axum::debug_handler]
+pub async fn revert(
+    State(state): State<Arc<AppState>>,
+    Path((id, version)): Path<(Uuid, i32)>,
+    headers: HeaderMap
+) -> Result<(HeaderMap, Json<Quote>), QuoteError> {
+    let caller = authorize_change(&state, &headers, &id).await?;
+
+    let quote = state.quote_repository
+        .revert_quote(&id, version, &caller.user)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok((etag(&quote), Json(quote)))
+}
+
+#[axum::debug_handler]
+pub async fn restore(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap
+) -> Result<(HeaderMap, Json<Quote>), QuoteError> {
+    let caller = authorize_change(&state, &headers, &id).await?;
+
+    let quote = state.quote_repository
+        .restore_quote(&id, &caller.user)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok((etag(&quote), Json(quote)))
+}
+
+#[axum::debug_handler]
+pub async fn trash(
+    State(state): State<Arc<AppState>>
+) -> Result<Json<Vec<Quote>>, QuoteError> {
+    let quotes = state.quote_repository
+        .list_trash()
+        .await?;
+
+    Ok(Json(quotes))
+}
+
+/// Two steps: a bare call answers 202 with a confirmation token, and calling
+/// again with `?confirm=<token>` trashes every quote. A token can only be
+/// tried once. Admin only unless `admin-reset` is turned off.
 #[axum::debug_handler]
 pub async fn reset(
     State(state): State<Arc<AppState>>,
-) -> StatusCode {
-    match state.quote_controller
-        .clean_db()
-        .await 
-    {
-        Ok(_) => StatusCode::OK,
-        Err(e) => {
-            println!("Error deleting table: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+    query: Result<Query<ResetQuery>, QueryRejection>,
+    headers: HeaderMap
+) -> Result<(StatusCode, Json<ResetResponse>), QuoteError> {
+    let Query(query) = query.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let caller = authorize_reset(&state, &headers).await?;
+
+    let mut pending = state.reset_confirmation.lock().await;
+
+    let Some(confirm) = query.confirm else {
+        let ttl = state.quotes_config.trash.reset_confirmation_ttl;
+        let token = Uuid::new_v4().simple().to_string();
+
+        *pending = Some(ResetConfirmation {
+            token: token.clone(),
+            expires_at: Instant::now() + Duration::from_secs(ttl)
+        });
+
+        return Ok((StatusCode::ACCEPTED, Json(ResetResponse::Pending {
+            confirm: token,
+            expires_in: ttl
+        })))
+    };
+
+    let confirmed = pending.take().is_some_and(|pending| {
+        pending.expires_at > Instant::now()
+            && constant_time_eq(pending.token.as_bytes(), confirm.as_bytes())
+    });
+
+    if !confirmed {
+        return Err(QuoteError::InvalidBody("Invalid or expired reset confirmation".to_string()))
+    }
+
+    let trashed = state.quote_repository
+        .clean_db(caller.as_ref().map(|caller| caller.user.as_str()))
+        .await?;
+
+    Ok((StatusCode::OK, Json(ResetResponse::Done { trashed })))
+}
+
+#[axum::debug_handler]
+pub async fn search(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<QuoteSearch>, QueryRejection>
+) -> Result<Json<SearchResults>, QuoteError> {
+    let Query(query) = query.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let query = query.validate()?;
+
+    let results = state.quote_repository
+        .search_quotes(&query)
+        .await?;
+
+    Ok(Json(results))
+}
+
+/// Streams every quote as JSON Lines or CSV, depending on Accept.
+#[axum::debug_handler]
+pub async fn export(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap
+) -> Result<Response, QuoteError> {
+    let accept = headers.get(ACCEPT)
+        .map(|accept| accept.to_str().map_err(|_| QuoteError::InvalidBody("Invalid Accept header".to_string())))
+        .transpose()?;
+
+    let format = TransferFormat::from_accept(accept).ok_or(StatusCode::NOT_ACCEPTABLE)?;
+
+    let quotes = state.quote_repository
+        .export_quotes()
+        .map(move |quote| {
+            quote
+                .map_err(|e| e.to_string())
+                .and_then(|quote| format.encode(&quote))
+                .inspect_err(|e| println!("Error exporting quotes: {}", e))
+        });
+
+    let body = stream::once(async move { Ok(format.preamble().to_vec()) }).chain(quotes);
+
+    Ok((
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", format.file_name()))
+        ],
+        Body::from_stream(body)
+    ).into_response())
+}
+
+/// Imports JSON Lines or CSV, depending on Content-Type. Either every line is
+/// imported or none is: any invalid line fails the import with 422 and the
+/// errors of every line. `?dry_run=true` only reports. Imported quotes belong
+/// to the caller.
+#[axum::debug_handler]
+pub async fn import(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<ImportQuery>, QueryRejection>,
+    headers: HeaderMap,
+    body: String
+) -> Result<(StatusCode, Json<ImportReport>), QuoteError> {
+    let Query(query) = query.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let caller = authenticate(&state, &headers).await?;
+
+    let format = match headers.get(CONTENT_TYPE) {
+        Some(content_type) => content_type.to_str()
+            .ok()
+            .and_then(TransferFormat::from_content_type)
+            .ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?,
+        None => TransferFormat::sniff(&body)
+    };
+
+    let lines = format.parse(&body).map_err(QuoteError::InvalidBody)?;
+
+    let mut errors = Vec::new();
+    let mut quotes = Vec::new();
+
+    for (line, quote) in lines {
+        match quote.and_then(|quote| quote.validate(&state.quotes_config.limits)) {
+            Ok(quote) => quotes.push((line, quote)),
+            Err(message) => errors.push(ImportError::new(line, message))
         }
     }
+
+    let dry_run = query.dry_run || !errors.is_empty();
+
+    let conflicts = state.quote_repository
+        .import_quotes(&quotes, &caller.user, dry_run)
+        .await?;
+
+    let valid = quotes.len() - conflicts.len();
+
+    errors.extend(conflicts);
+    errors.sort_by_key(|error| error.line);
+
+    // A conflict rolls the whole import back
+    let imported = match dry_run || !errors.is_empty() {
+        true => 0,
+        false => quotes.len()
+    };
+
+    let status = match errors.is_empty() {
+        true => StatusCode::OK,
+        false => StatusCode::UNPROCESSABLE_ENTITY
+    };
+
+    Ok((status, Json(ImportReport {
+        dry_run: query.dry_run,
+        valid,
+        imported,
+        errors
+    })))
+}
+
+#[axum::debug_handler]
+pub async fn random(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<RandomQuery>, QueryRejection>
+) -> Result<(HeaderMap, Json<Quote>), QuoteError> {
+    let Query(query) = query.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let fraction = rand::thread_rng().gen::<f64>();
+    let author = query.author.as_deref()
+        .map(str::trim)
+        .filter(|author| !author.is_empty());
+
+    let quote = state.quote_repository
+        .pick_quote(fraction, author, None)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+
+    Ok((headers, Json(quote)))
+}
+
+/// Everyone gets the same quote for a given UTC date. Only quotes created
+/// before that day are candidates, so quotes drafted during the day don't
+/// change it (unless there were none before).
+#[axum::debug_handler]
+pub async fn daily(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<DailyQuery>, QueryRejection>
+) -> Result<(HeaderMap, Json<Quote>), QuoteError> {
+    let Query(query) = query.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let now = Utc::now();
+    let date = query.date.unwrap_or(now.date_naive());
+    let fraction = daily_fraction(&date);
+    let repository = &state.quote_repository;
+
+    let quote = match repository.pick_quote(fraction, None, Some(&start_of_day(&date))).await? {
+        Some(quote) => quote,
+        None => repository
+            .pick_quote(fraction, None, None)
+            .await?
+            .ok_or(StatusCode::NOT_FOUND)?
+    };
+
+    let mut headers = HeaderMap::new();
+    if date == now.date_naive() {
+        let max_age = format!("public, max-age={}", seconds_until_next_day(&date, &now));
+        if let Ok(value) = HeaderValue::from_str(&max_age) {
+            headers.insert(CACHE_CONTROL, value);
+        }
+    }
+
+    Ok((headers, Json(quote)))
 }
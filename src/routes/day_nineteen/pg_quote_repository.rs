@@ -0,0 +1,788 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::{stream::BoxStream, TryStreamExt};
+use sqlx::{Error, PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{
+    collections::{
+        Collection, CollectionForCreation, CollectionForUpdate, CollectionOutcome,
+        CollectionSummary, CollectionWithQuotes
+    },
+    quote_repository::{QuoteRepository, UpdateOutcome},
+    search::{QuoteSearch, SearchHit, SearchResults},
+    transfer::{ImportError, QuoteForImport},
+    Quote, QuoteForCreation, QuoteForUpdate, QuoteHistory, QuoteVersion
+};
+
+/// Shared by the page and count queries of `search_quotes`. The tsvector
+/// expression must match the one of `quotes_quote_search_idx`.
+const SEARCH_FILTER: &str =
+    "WHERE ($1::TEXT IS NULL OR to_tsvector('english', quote) @@ websearch_to_tsquery('english', $1))
+    AND ($2::TEXT IS NULL OR lower(author) = lower($2))
+    AND ($3::TIMESTAMPTZ IS NULL OR created_at >= $3)
+    AND ($4::TIMESTAMPTZ IS NULL OR created_at <= $4)
+    AND deleted_at IS NULL";
+
+/// `ts_headline` doesn't escape the quote around its marks. It marks matches
+/// with control characters instead, which quotes can't contain, so the quote
+/// can be escaped before they become `<mark>` tags.
+const HIGHLIGHT: &str =
+    "replace(replace(replace(replace(replace(
+        ts_headline('english', translate(quote, chr(2) || chr(3), ''), websearch_to_tsquery('english', $1),
+            'StartSel=' || chr(2) || ', StopSel=' || chr(3) || ', HighlightAll=true'),
+        '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), chr(2), '<mark>'), chr(3), '</mark>')";
+
+pub struct PgQuoteRepository {
+    pool: PgPool
+}
+
+impl PgQuoteRepository {
+    pub fn build(pool: PgPool) -> Self {
+        Self {
+            pool
+        }
+    }
+}
+
+#[async_trait]
+impl QuoteRepository for PgQuoteRepository {
+    async fn create_quote(
+        &self,
+        quote_for_creation: QuoteForCreation,
+        owner: Option<&str>
+    ) -> Result<Quote, Error> {
+        let id = Uuid::new_v4();
+        let created_at = Utc::now();
+        let version = 1;
+
+        let quote = sqlx::query_as::<_, Quote>(
+            "INSERT INTO quotes (id, author, quote, created_at, version, owner, changed_by) 
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            RETURNING *;"
+        )
+        .bind(id)
+        .bind(&quote_for_creation.author)
+        .bind(&quote_for_creation.quote)
+        .bind(created_at)
+        .bind(version)
+        .bind(owner)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(quote)
+    }
+
+    async fn get_quote(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Quote>, Error> {
+        let quote =  sqlx::query_as::<_, Quote>(
+            "SELECT * FROM quotes WHERE id = $1 AND deleted_at IS NULL;"
+        )
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(quote)
+    }
+
+    async fn find_quote(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Quote>, Error> {
+        sqlx::query_as::<_, Quote>("SELECT * FROM quotes WHERE id = $1;")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn delete_quote(
+        &self,
+        id: &Uuid,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(version) = archive_quote(&mut tx, id, None, "delete").await? else {
+            return Ok(None)
+        };
+
+        let quote = sqlx::query_as::<_, Quote>(
+            "UPDATE quotes SET deleted_at = $1, changed_by = $3, version = $4
+            WHERE id = $2
+            RETURNING *;"
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .bind(changed_by)
+        .bind(version + 1)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(quote))
+    }
+
+    async fn restore_quote(
+        &self,
+        id: &Uuid,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error> {
+        let quote = sqlx::query_as::<_, Quote>(
+            "UPDATE quotes SET deleted_at = NULL, changed_by = $2
+            WHERE id = $1 AND deleted_at IS NOT NULL
+            RETURNING *;"
+        )
+        .bind(id)
+        .bind(changed_by)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(quote)
+    }
+
+    async fn list_trash(&self) -> Result<Vec<Quote>, Error> {
+        let quotes = sqlx::query_as::<_, Quote>(
+            "SELECT * FROM quotes WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC, id;"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(quotes)
+    }
+
+    async fn update_quote(
+        &self,
+        id: &Uuid,
+        quote_for_update: &QuoteForUpdate,
+        expected_version: Option<i32>,
+        changed_by: &str
+    ) -> Result<UpdateOutcome, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(version) = archive_quote(&mut tx, id, expected_version, "update").await? else {
+            let current_version = sqlx::query_scalar::<_, i32>(
+                "SELECT version FROM quotes WHERE id = $1 AND deleted_at IS NULL;"
+            )
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            return Ok(match current_version {
+                Some(current_version) => UpdateOutcome::Conflict(current_version),
+                None => UpdateOutcome::NotFound
+            })
+        };
+
+        let quote = sqlx::query_as::<_, Quote>(
+            "UPDATE quotes 
+            SET author = $1, quote = $2, version = $3, changed_by = $6
+            WHERE id = $4 AND version = $5
+            RETURNING *;"
+        )
+        .bind(&quote_for_update.author)
+        .bind(&quote_for_update.quote)
+        .bind(version + 1)
+        .bind(id)
+        .bind(version)
+        .bind(changed_by)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(UpdateOutcome::Updated(quote))
+    }
+
+    /// Only the quotes archived by the same statement are trashed, so quotes
+    /// created in the meantime neither skip the history nor get trashed.
+    async fn clean_db(&self, changed_by: Option<&str>) -> Result<u64, Error> {
+        let result = sqlx::query(
+            "WITH archived AS (
+                INSERT INTO quote_versions (quote_id, version, author, quote, created_at, superseded_by, changed_by)
+                SELECT id, version, author, quote, created_at, 'reset', changed_by FROM quotes
+                WHERE deleted_at IS NULL
+                FOR UPDATE
+                RETURNING quote_id
+            )
+            UPDATE quotes SET deleted_at = $1, changed_by = $2, version = version + 1
+            WHERE id IN (SELECT quote_id FROM archived);"
+        )
+        .bind(Utc::now())
+        .bind(changed_by)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn purge_deleted(
+        &self,
+        deleted_before: &DateTime<Utc>
+    ) -> Result<u64, Error> {
+        let result = sqlx::query("DELETE FROM quotes WHERE deleted_at < $1;")
+            .bind(deleted_before)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn get_history(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<QuoteHistory>, Error> {
+        let pool = &self.pool;
+        let current = sqlx::query_as::<_, Quote>("SELECT * FROM quotes WHERE id = $1;")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        let versions = sqlx::query_as::<_, QuoteVersion>(
+            "SELECT * FROM quote_versions WHERE quote_id = $1 ORDER BY version;"
+        )
+        .bind(id)
+        .fetch_all(pool)
+        .await?;
+
+        if current.is_none() && versions.is_empty() {
+            return Ok(None)
+        }
+
+        Ok(Some(QuoteHistory {
+            current,
+            versions
+        }))
+    }
+
+    async fn revert_quote(
+        &self,
+        id: &Uuid,
+        version: i32,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(past) = sqlx::query_as::<_, QuoteVersion>(
+            "SELECT * FROM quote_versions WHERE quote_id = $1 AND version = $2;"
+        )
+        .bind(id)
+        .bind(version)
+        .fetch_optional(&mut *tx)
+        .await? else {
+            return Ok(None)
+        };
+
+        sqlx::query("UPDATE quotes SET deleted_at = NULL WHERE id = $1;")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        let quote = match archive_quote(&mut tx, id, None, "revert").await? {
+            Some(current_version) => sqlx::query_as::<_, Quote>(
+                "UPDATE quotes
+                SET author = $1, quote = $2, version = $3, changed_by = $6
+                WHERE id = $4 AND version = $5
+                RETURNING *;"
+            )
+            .bind(&past.author)
+            .bind(&past.quote)
+            .bind(current_version + 1)
+            .bind(id)
+            .bind(current_version)
+            .bind(changed_by)
+            .fetch_optional(&mut *tx)
+            .await?,
+            None => {
+                let latest_version = sqlx::query_scalar::<_, i32>(
+                    "SELECT MAX(version) FROM quote_versions WHERE quote_id = $1;"
+                )
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?;
+
+                Some(sqlx::query_as::<_, Quote>(
+                    "INSERT INTO quotes (id, author, quote, created_at, version, changed_by)
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    RETURNING *;"
+                )
+                .bind(id)
+                .bind(&past.author)
+                .bind(&past.quote)
+                .bind(past.created_at)
+                .bind(latest_version + 1)
+                .bind(changed_by)
+                .fetch_one(&mut *tx)
+                .await?)
+            }
+        };
+
+        tx.commit().await?;
+
+        Ok(quote)
+    }
+
+    fn export_quotes(&self) -> BoxStream<'static, Result<Quote, Error>> {
+        let pool = self.pool.clone();
+
+        Box::pin(async_stream::try_stream! {
+            let mut quotes = sqlx::query_as::<_, Quote>(
+                "SELECT * FROM quotes WHERE deleted_at IS NULL ORDER BY created_at, id;"
+            )
+            .fetch(&pool);
+
+            while let Some(quote) = quotes.try_next().await? {
+                yield quote;
+            }
+        })
+    }
+
+    async fn import_quotes(
+        &self,
+        quotes: &[(u64, QuoteForImport)],
+        owner: &str,
+        dry_run: bool
+    ) -> Result<Vec<ImportError>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut errors = Vec::new();
+
+        for (line, quote) in quotes {
+            let id = quote.id.unwrap_or_else(Uuid::new_v4);
+
+            let inserted = sqlx::query_scalar::<_, Uuid>(
+                "INSERT INTO quotes (id, author, quote, created_at, version, owner, changed_by)
+                SELECT $1, $2, $3, $4, 1, $5, $5
+                WHERE NOT EXISTS (SELECT 1 FROM quote_versions WHERE quote_id = $1)
+                ON CONFLICT (id) DO NOTHING
+                RETURNING id;"
+            )
+            .bind(id)
+            .bind(&quote.author)
+            .bind(&quote.quote)
+            .bind(quote.created_at.unwrap_or_else(Utc::now))
+            .bind(owner)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if inserted.is_none() {
+                errors.push(ImportError::new(*line, format!("Quote {} already exists or was purged", id)));
+            }
+        }
+
+        if dry_run || !errors.is_empty() {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+
+        Ok(errors)
+    }
+
+    /// The first quote whose `pick_key` comes at or after `fraction`,
+    /// wrapping around to the lowest key. Both halves are a single index
+    /// lookup, which the author condition is only added for when needed.
+    async fn pick_quote(
+        &self,
+        fraction: f64,
+        author: Option<&str>,
+        created_before: Option<&DateTime<Utc>>
+    ) -> Result<Option<Quote>, Error> {
+        let filter = format!(
+            "deleted_at IS NULL
+            AND ($2::TIMESTAMPTZ IS NULL OR created_at < $2)
+            {}",
+            match author {
+                Some(_) => "AND lower(author) = lower($3)",
+                None => ""
+            }
+        );
+
+        let sql = format!(
+            "(SELECT * FROM quotes WHERE {filter} AND pick_key >= $1 ORDER BY pick_key LIMIT 1)
+            UNION ALL
+            (SELECT * FROM quotes WHERE {filter} ORDER BY pick_key LIMIT 1)
+            LIMIT 1;",
+            filter = filter
+        );
+
+        let mut query = sqlx::query_as::<_, Quote>(&sql)
+            .bind(fraction)
+            .bind(created_before);
+
+        if let Some(author) = author {
+            query = query.bind(author);
+        }
+
+        query.fetch_optional(&self.pool).await
+    }
+
+    async fn tag_quote(
+        &self,
+        id: &Uuid,
+        tag: &str
+    ) -> Result<Option<Vec<String>>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        if !is_live(&mut *tx, id).await? {
+            return Ok(None)
+        }
+
+        sqlx::query("INSERT INTO tags (name) VALUES ($1) ON CONFLICT (name) DO NOTHING;")
+            .bind(tag)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO quote_tags (quote_id, tag) VALUES ($1, $2)
+            ON CONFLICT (quote_id, tag) DO NOTHING;"
+        )
+        .bind(id)
+        .bind(tag)
+        .execute(&mut *tx)
+        .await?;
+
+        let tags = quote_tags(&mut *tx, id).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(tags))
+    }
+
+    async fn untag_quote(
+        &self,
+        id: &Uuid,
+        tag: &str
+    ) -> Result<Option<Vec<String>>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        if !is_live(&mut *tx, id).await? {
+            return Ok(None)
+        }
+
+        sqlx::query("DELETE FROM quote_tags WHERE quote_id = $1 AND tag = $2;")
+            .bind(id)
+            .bind(tag)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "DELETE FROM tags WHERE name = $1
+            AND NOT EXISTS (SELECT 1 FROM quote_tags WHERE tag = $1);"
+        )
+        .bind(tag)
+        .execute(&mut *tx)
+        .await?;
+
+        let tags = quote_tags(&mut *tx, id).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(tags))
+    }
+
+    async fn get_tags(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Vec<String>>, Error> {
+        let pool = &self.pool;
+        if !is_live(pool, id).await? {
+            return Ok(None)
+        }
+
+        Ok(Some(quote_tags(pool, id).await?))
+    }
+
+    async fn list_tagged(
+        &self,
+        tag: &str
+    ) -> Result<Vec<Quote>, Error> {
+        let pool = &self.pool;
+        let quotes = sqlx::query_as::<_, Quote>(
+            "SELECT quotes.* FROM quotes
+            JOIN quote_tags ON quote_tags.quote_id = quotes.id
+            WHERE quote_tags.tag = $1 AND quotes.deleted_at IS NULL
+            ORDER BY quotes.created_at, quotes.id;"
+        )
+        .bind(tag)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(quotes)
+    }
+
+    async fn create_collection(
+        &self,
+        collection_for_creation: &CollectionForCreation,
+        owner: &str
+    ) -> Result<CollectionOutcome, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let collection = sqlx::query_as::<_, Collection>(
+            "INSERT INTO collections (id, name, created_at, owner)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *;"
+        )
+        .bind(Uuid::new_v4())
+        .bind(&collection_for_creation.name)
+        .bind(Utc::now())
+        .bind(owner)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let unknown = set_collection_quotes(&mut tx, &collection.id, &collection_for_creation.quotes).await?;
+        if !unknown.is_empty() {
+            return Ok(CollectionOutcome::UnknownQuotes(unknown))
+        }
+
+        let quotes = collection_quotes(&mut *tx, &collection.id).await?;
+
+        tx.commit().await?;
+
+        Ok(CollectionOutcome::Saved(CollectionWithQuotes {
+            collection,
+            quotes
+        }))
+    }
+
+    async fn list_collections(&self) -> Result<Vec<CollectionSummary>, Error> {
+        let collections = sqlx::query_as::<_, CollectionSummary>(
+            "SELECT collections.*, COUNT(collection_quotes.quote_id) AS size
+            FROM collections
+            LEFT JOIN collection_quotes ON collection_quotes.collection_id = collections.id
+            GROUP BY collections.id
+            ORDER BY collections.name;"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(collections)
+    }
+
+    async fn get_collection(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<CollectionWithQuotes>, Error> {
+        let pool = &self.pool;
+        let Some(collection) = sqlx::query_as::<_, Collection>(
+            "SELECT * FROM collections WHERE id = $1;"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await? else {
+            return Ok(None)
+        };
+
+        let quotes = collection_quotes(pool, id).await?;
+
+        Ok(Some(CollectionWithQuotes {
+            collection,
+            quotes
+        }))
+    }
+
+    async fn update_collection(
+        &self,
+        id: &Uuid,
+        collection_for_update: &CollectionForUpdate
+    ) -> Result<CollectionOutcome, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let collection = sqlx::query_as::<_, Collection>(
+            "UPDATE collections SET name = COALESCE($1, name)
+            WHERE id = $2
+            RETURNING *;"
+        )
+        .bind(&collection_for_update.name)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(collection) = collection else {
+            return Ok(CollectionOutcome::NotFound)
+        };
+
+        if let Some(quotes) = &collection_for_update.quotes {
+            sqlx::query("DELETE FROM collection_quotes WHERE collection_id = $1;")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+
+            let unknown = set_collection_quotes(&mut tx, id, quotes).await?;
+            if !unknown.is_empty() {
+                return Ok(CollectionOutcome::UnknownQuotes(unknown))
+            }
+        }
+
+        let quotes = collection_quotes(&mut *tx, id).await?;
+
+        tx.commit().await?;
+
+        Ok(CollectionOutcome::Saved(CollectionWithQuotes {
+            collection,
+            quotes
+        }))
+    }
+
+    async fn delete_collection(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Collection>, Error> {
+        let pool = &self.pool;
+        let collection = sqlx::query_as::<_, Collection>(
+            "DELETE FROM collections WHERE id = $1 RETURNING *;"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(collection)
+    }
+
+    async fn search_quotes(
+        &self,
+        search: &QuoteSearch
+    ) -> Result<SearchResults, Error> {
+        let pool = &self.pool;
+        let results = sqlx::query_as::<_, SearchHit>(&format!(
+            "SELECT *,
+                CASE WHEN $1::TEXT IS NULL THEN NULL
+                    ELSE ts_rank(to_tsvector('english', quote), websearch_to_tsquery('english', $1))
+                END AS rank,
+                CASE WHEN $1::TEXT IS NULL THEN NULL
+                    ELSE {}
+                END AS highlight
+            FROM quotes
+            {}
+            ORDER BY rank DESC NULLS LAST, created_at DESC, id
+            LIMIT $5 OFFSET $6;",
+            HIGHLIGHT,
+            SEARCH_FILTER
+        ))
+        .bind(&search.q)
+        .bind(&search.author)
+        .bind(search.since)
+        .bind(search.until)
+        .bind(search.per_page())
+        .bind(search.offset())
+        .fetch_all(pool)
+        .await?;
+
+        let total = sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT COUNT(*) FROM quotes {};",
+            SEARCH_FILTER
+        ))
+        .bind(&search.q)
+        .bind(&search.author)
+        .bind(search.since)
+        .bind(search.until)
+        .fetch_one(pool)
+        .await?;
+
+        let next_page = (search.offset() + search.per_page() < total)
+            .then(|| search.page() + 1);
+
+        Ok(SearchResults {
+            results,
+            page: search.page(),
+            per_page: search.per_page(),
+            total,
+            next_page
+        })
+    }
+}
+
+/// Copies the current row of a quote into its history before it gets
+/// overwritten or trashed, returning the archived version. The row stays
+/// locked until the transaction ends, so concurrent writers queue up and then
+/// see the version left by the previous one. `None` when the quote doesn't
+/// exist, is trashed or isn't at `expected_version`.
+async fn archive_quote(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+    expected_version: Option<i32>,
+    superseded_by: &str
+) -> Result<Option<i32>, Error> {
+    sqlx::query_scalar::<_, i32>(
+        "INSERT INTO quote_versions (quote_id, version, author, quote, created_at, superseded_by, changed_by)
+        SELECT id, version, author, quote, created_at, $3, changed_by FROM quotes
+        WHERE id = $1 AND ($2::INT IS NULL OR version = $2) AND deleted_at IS NULL
+        FOR UPDATE
+        RETURNING version;"
+    )
+    .bind(id)
+    .bind(expected_version)
+    .bind(superseded_by)
+    .fetch_optional(&mut **tx)
+    .await
+}
+
+async fn is_live(
+    executor: impl PgExecutor<'_>,
+    id: &Uuid
+) -> Result<bool, Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM quotes WHERE id = $1 AND deleted_at IS NULL);"
+    )
+    .bind(id)
+    .fetch_one(executor)
+    .await
+}
+
+async fn quote_tags(
+    executor: impl PgExecutor<'_>,
+    id: &Uuid
+) -> Result<Vec<String>, Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT tag FROM quote_tags WHERE quote_id = $1 ORDER BY tag;"
+    )
+    .bind(id)
+    .fetch_all(executor)
+    .await
+}
+
+/// Live quotes of a collection, in the collection's order.
+async fn collection_quotes(
+    executor: impl PgExecutor<'_>,
+    collection_id: &Uuid
+) -> Result<Vec<Quote>, Error> {
+    sqlx::query_as::<_, Quote>(
+        "SELECT quotes.* FROM quotes
+        JOIN collection_quotes ON collection_quotes.quote_id = quotes.id
+        WHERE collection_quotes.collection_id = $1 AND quotes.deleted_at IS NULL
+        ORDER BY collection_quotes.position;"
+    )
+    .bind(collection_id)
+    .fetch_all(executor)
+    .await
+}
+
+/// Adds quotes to an empty collection in the given order, returning the ids
+/// that don't match a live quote. The caller must not commit if any did.
+async fn set_collection_quotes(
+    tx: &mut Transaction<'_, Postgres>,
+    collection_id: &Uuid,
+    quotes: &[Uuid]
+) -> Result<Vec<Uuid>, Error> {
+    let mut unknown = Vec::new();
+
+    for (position, quote_id) in (0..).zip(quotes) {
+        let added = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO collection_quotes (collection_id, quote_id, position)
+            SELECT $1, id, $3 FROM quotes WHERE id = $2 AND deleted_at IS NULL
+            RETURNING quote_id;"
+        )
+        .bind(collection_id)
+        .bind(quote_id)
+        .bind(position)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        if added.is_none() {
+            unknown.push(*quote_id);
+        }
+    }
+
+    Ok(unknown)
+}
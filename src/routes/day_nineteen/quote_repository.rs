@@ -0,0 +1,335 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::BoxStream;
+use sqlx::Error;
+use uuid::Uuid;
+
+use super::{
+    collections::{
+        Collection, CollectionForCreation, CollectionForUpdate, CollectionOutcome,
+        CollectionSummary, CollectionWithQuotes
+    },
+    search::{QuoteSearch, SearchResults},
+    transfer::{ImportError, QuoteForImport},
+    Quote, QuoteForCreation, QuoteForUpdate, QuoteHistory
+};
+
+pub enum UpdateOutcome {
+    Updated(Quote),
+    NotFound,
+    /// The quote has moved on from the expected version.
+    Conflict(i32)
+}
+
+/// Where quotes are kept, along with their history, tags and collections.
+#[async_trait]
+pub trait QuoteRepository: Send + Sync {
+    /// `owner` is `None` for anonymous drafts.
+    async fn create_quote(
+        &self,
+        quote_for_creation: QuoteForCreation,
+        owner: Option<&str>
+    ) -> Result<Quote, Error>;
+
+    /// Live quotes only.
+    async fn get_quote(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Quote>, Error>;
+
+    /// Trashed or not.
+    async fn find_quote(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Quote>, Error>;
+
+    /// Moves the quote to the trash, where it stays restorable until purged.
+    /// The live version goes to the history and the trashed quote gets the
+    /// next version.
+    async fn delete_quote(
+        &self,
+        id: &Uuid,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error>;
+
+    async fn restore_quote(
+        &self,
+        id: &Uuid,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error>;
+
+    /// Most recently deleted first.
+    async fn list_trash(&self) -> Result<Vec<Quote>, Error>;
+
+    /// Compare-and-set: with an `expected_version`, the quote is only updated
+    /// if it is still at that version. The replaced version goes to the
+    /// history.
+    async fn update_quote(
+        &self,
+        id: &Uuid,
+        quote_for_update: &QuoteForUpdate,
+        expected_version: Option<i32>,
+        changed_by: &str
+    ) -> Result<UpdateOutcome, Error>;
+
+    /// Trashes every quote like `delete_quote` does, returning how many were
+    /// trashed. `changed_by` is `None` when the reset was anonymous.
+    async fn clean_db(&self, changed_by: Option<&str>) -> Result<u64, Error>;
+
+    /// Permanently deletes quotes trashed before `deleted_before`, along with
+    /// their tags and places in collections. Their history stays, so they can
+    /// still be reverted to.
+    async fn purge_deleted(
+        &self,
+        deleted_before: &DateTime<Utc>
+    ) -> Result<u64, Error>;
+
+    /// Past versions oldest first, along with the current quote, trashed or
+    /// not. `None` when the quote never existed.
+    async fn get_history(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<QuoteHistory>, Error>;
+
+    /// Restores a past version as a new version, so the revert itself shows up
+    /// in the history. Trashed quotes are brought back. Quotes that were
+    /// deleted for good come back without an owner.
+    async fn revert_quote(
+        &self,
+        id: &Uuid,
+        version: i32,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error>;
+
+    /// Every live quote, oldest first.
+    fn export_quotes(&self) -> BoxStream<'static, Result<Quote, Error>>;
+
+    /// Inserts all quotes at once, only if none of them clashes with an
+    /// existing id and this isn't a dry run. Returns the clashes. Ids of
+    /// purged quotes clash too, as their history is still around. Imported
+    /// quotes belong to `owner`.
+    async fn import_quotes(
+        &self,
+        quotes: &[(u64, QuoteForImport)],
+        owner: &str,
+        dry_run: bool
+    ) -> Result<Vec<ImportError>, Error>;
+
+    /// Picks a matching quote for `fraction` (in [0, 1)), the same one for the
+    /// same fraction as long as the quotes don't change. The memory and SQLite
+    /// stores pick by position in creation order. The Postgres store picks by
+    /// each quote's random `pick_key`, so quotes are only equally likely on
+    /// average, in exchange for an index lookup instead of a count.
+    async fn pick_quote(
+        &self,
+        fraction: f64,
+        author: Option<&str>,
+        created_before: Option<&DateTime<Utc>>
+    ) -> Result<Option<Quote>, Error>;
+
+    /// Tags are created on first use. `None` when the quote doesn't exist or
+    /// is trashed, otherwise the quote's tags.
+    async fn tag_quote(
+        &self,
+        id: &Uuid,
+        tag: &str
+    ) -> Result<Option<Vec<String>>, Error>;
+
+    /// Tags no quote uses anymore are dropped.
+    async fn untag_quote(
+        &self,
+        id: &Uuid,
+        tag: &str
+    ) -> Result<Option<Vec<String>>, Error>;
+
+    async fn get_tags(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Vec<String>>, Error>;
+
+    /// Live quotes with the tag, oldest first.
+    async fn list_tagged(
+        &self,
+        tag: &str
+    ) -> Result<Vec<Quote>, Error>;
+
+    /// A name that's already taken surfaces as a unique violation.
+    async fn create_collection(
+        &self,
+        collection_for_creation: &CollectionForCreation,
+        owner: &str
+    ) -> Result<CollectionOutcome, Error>;
+
+    /// By name.
+    async fn list_collections(&self) -> Result<Vec<CollectionSummary>, Error>;
+
+    async fn get_collection(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<CollectionWithQuotes>, Error>;
+
+    async fn update_collection(
+        &self,
+        id: &Uuid,
+        collection_for_update: &CollectionForUpdate
+    ) -> Result<CollectionOutcome, Error>;
+
+    /// The quotes themselves are left alone.
+    async fn delete_collection(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Collection>, Error>;
+
+    async fn search_quotes(
+        &self,
+        search: &QuoteSearch
+    ) -> Result<SearchResults, Error>;
+}
+
+/// Behaviour every store shares. Each store's tests run these against a fresh
+/// repository of their own.
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn quote(author: &str, quote: &str) -> QuoteForCreation {
+        QuoteForCreation {
+            author: author.to_string(),
+            quote: quote.to_string()
+        }
+    }
+
+    pub(crate) async fn keeps_history_and_reverts(repository: Arc<dyn QuoteRepository>) {
+        let quote = repository.create_quote(quote("Santa", "Ho ho ho"), Some("santa")).await.unwrap();
+
+        let update = QuoteForUpdate {
+            author: "Santa".to_string(),
+            quote: "Ho ho".to_string(),
+            version: None
+        };
+
+        let UpdateOutcome::Updated(_) = repository.update_quote(&quote.id, &update, Some(1), "santa").await.unwrap() else {
+            panic!("update failed")
+        };
+        let UpdateOutcome::Conflict(2) = repository.update_quote(&quote.id, &update, Some(1), "santa").await.unwrap() else {
+            panic!("stale update went through")
+        };
+
+        repository.delete_quote(&quote.id, "santa").await.unwrap();
+        let reverted = repository.revert_quote(&quote.id, 1, "elf").await.unwrap().unwrap();
+
+        assert_eq!((reverted.quote.as_str(), reverted.version, reverted.deleted_at), ("Ho ho ho", 4, None));
+        assert_eq!(reverted.owner.as_deref(), Some("santa"));
+
+        let history = repository.get_history(&quote.id).await.unwrap().unwrap();
+        let superseded_by = history.versions.iter().map(|past| past.superseded_by.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(superseded_by, ["update", "delete", "revert"]);
+    }
+
+    pub(crate) async fn purges_tags_and_collection_places(repository: Arc<dyn QuoteRepository>) {
+        let kept = repository.create_quote(quote("Elf", "Back to work"), None).await.unwrap();
+        let purged = repository.create_quote(quote("Grinch", "Bah"), None).await.unwrap();
+
+        repository.tag_quote(&purged.id, "grumpy").await.unwrap();
+        let CollectionOutcome::Saved(collection) = repository.create_collection(&CollectionForCreation {
+            name: "Workshop".to_string(),
+            quotes: vec![purged.id, kept.id]
+        }, "santa").await.unwrap() else {
+            panic!("collection wasn't saved")
+        };
+
+        repository.delete_quote(&purged.id, "santa").await.unwrap();
+        assert_eq!(repository.purge_deleted(&Utc::now()).await.unwrap(), 1);
+
+        assert!(repository.list_tagged("grumpy").await.unwrap().is_empty());
+
+        let history = repository.get_history(&purged.id).await.unwrap().unwrap();
+        assert!(history.current.is_none());
+        assert_eq!(history.versions.len(), 1);
+
+        let collections = repository.list_collections().await.unwrap();
+        assert_eq!((collections[0].collection.id, collections[0].size), (collection.collection.id, 1));
+
+        let reimport = QuoteForImport {
+            id: Some(purged.id),
+            author: "Grinch".to_string(),
+            quote: "Bah".to_string(),
+            created_at: None
+        };
+        assert_eq!(repository.import_quotes(&[(1, reimport)], "santa", false).await.unwrap().len(), 1);
+
+        let reverted = repository.revert_quote(&purged.id, 1, "santa").await.unwrap().unwrap();
+        assert_eq!((reverted.quote.as_str(), reverted.version, reverted.owner), ("Bah", 2, None));
+    }
+
+    pub(crate) async fn refuses_taken_collection_names(repository: Arc<dyn QuoteRepository>) {
+        let collection = CollectionForCreation {
+            name: "Carols".to_string(),
+            quotes: Vec::new()
+        };
+
+        repository.create_collection(&collection, "santa").await.unwrap();
+        let Err(error) = repository.create_collection(&collection, "elf").await else {
+            panic!("name was taken twice")
+        };
+
+        assert!(error.as_database_error().is_some_and(|e| e.is_unique_violation()));
+    }
+
+    pub(crate) async fn rolls_back_imports_with_clashes(repository: Arc<dyn QuoteRepository>) {
+        let existing = repository.create_quote(quote("Santa", "Ho ho ho"), None).await.unwrap();
+
+        let import = |id| QuoteForImport {
+            id,
+            author: "Elf".to_string(),
+            quote: "Jingle".to_string(),
+            created_at: None
+        };
+
+        let errors = repository.import_quotes(&[(1, import(None)), (2, import(Some(existing.id)))], "elf", false).await.unwrap();
+
+        assert_eq!(errors.iter().map(|error| error.line).collect::<Vec<_>>(), [2]);
+        assert_eq!(repository.search_quotes(&QuoteSearch::default()).await.unwrap().total, 1);
+    }
+
+    pub(crate) async fn searches_newest_first_with_highlights(repository: Arc<dyn QuoteRepository>) {
+        repository.create_quote(quote("Santa", "Ho ho ho"), None).await.unwrap();
+        repository.create_quote(quote("Elf", "HO, said the elf"), None).await.unwrap();
+        repository.create_quote(quote("Grinch", "Bah"), None).await.unwrap();
+
+        let results = repository.search_quotes(&QuoteSearch {
+            q: Some("ho -elf".to_string()),
+            per_page: Some(1),
+            ..QuoteSearch::default()
+        }).await.unwrap();
+
+        assert_eq!((results.total, results.next_page), (1, None));
+        assert_eq!(results.results[0].highlight.as_deref(), Some("<mark>Ho</mark> <mark>ho</mark> <mark>ho</mark>"));
+
+        let results = repository.search_quotes(&QuoteSearch {
+            q: Some("ho".to_string()),
+            per_page: Some(1),
+            ..QuoteSearch::default()
+        }).await.unwrap();
+
+        assert_eq!((results.total, results.next_page), (2, Some(2)));
+        assert_eq!(results.results[0].quote.author, "Elf");
+    }
+
+    pub(crate) async fn picks_quotes_by_position(repository: Arc<dyn QuoteRepository>) {
+        repository.create_quote(quote("Santa", "Ho ho ho"), None).await.unwrap();
+        repository.create_quote(quote("Elf", "Jingle"), None).await.unwrap();
+        repository.create_quote(quote("Santa", "Merry Christmas"), None).await.unwrap();
+
+        let picked = repository.pick_quote(0.99, Some("santa"), None).await.unwrap().unwrap();
+        assert_eq!(picked.quote, "Merry Christmas");
+
+        let picked = repository.pick_quote(0.5, None, None).await.unwrap().unwrap();
+        assert_eq!(picked.quote, "Jingle");
+
+        assert!(repository.pick_quote(0.0, Some("Grinch"), None).await.unwrap().is_none());
+    }
+}
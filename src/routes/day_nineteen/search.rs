@@ -0,0 +1,272 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use html_escape::encode_text;
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use super::Quote;
+
+const DEFAULT_PER_PAGE: i64 = 10;
+const MAX_PER_PAGE: i64 = 100;
+
+/// Query parameters of `/19/search`. `q` uses web search syntax
+/// (`"exact phrase"`, `or`, `-excluded`), `since` and `until` are RFC 3339
+/// timestamps bounding `created_at`, and pages start at 1.
+#[derive(Deserialize, Debug, Default)]
+pub struct QuoteSearch {
+    pub q: Option<String>,
+    pub author: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>
+}
+
+impl QuoteSearch {
+    pub fn validate(self) -> Result<Self, StatusCode> {
+        if self.page.is_some_and(|page| page < 1) {
+            println!("Search page must be at least 1");
+            return Err(StatusCode::BAD_REQUEST)
+        }
+
+        if self.per_page.is_some_and(|per_page| !(1..=MAX_PER_PAGE).contains(&per_page)) {
+            println!("Search per_page must be between 1 and {}", MAX_PER_PAGE);
+            return Err(StatusCode::BAD_REQUEST)
+        }
+
+        if (self.page() - 1).checked_mul(self.per_page()).is_none() {
+            println!("Search page {} is out of range", self.page());
+            return Err(StatusCode::BAD_REQUEST)
+        }
+
+        if let (Some(since), Some(until)) = (self.since, self.until) {
+            if since > until {
+                println!("Search since is after until");
+                return Err(StatusCode::BAD_REQUEST)
+            }
+        }
+
+        Ok(Self {
+            q: non_blank(self.q),
+            author: non_blank(self.author),
+            ..self
+        })
+    }
+
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(DEFAULT_PER_PAGE)
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page() - 1) * self.per_page()
+    }
+}
+
+fn non_blank(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// `highlight` is only set when searching with `q`, and `rank` only by the
+/// Postgres store. It is HTML: the escaped quote with matches wrapped in
+/// `<mark>`, safe to insert into a page as is.
+#[derive(FromRow, Debug, Serialize)]
+pub struct SearchHit {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub quote: Quote,
+    pub rank: Option<f32>,
+    pub highlight: Option<String>
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResults {
+    pub results: Vec<SearchHit>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+    pub next_page: Option<i64>
+}
+
+/// `q` for the stores without full text search: words and `"phrases"` are
+/// found anywhere in the quote, ignoring case but without stemming. `or`
+/// joins alternatives and `-` excludes a word or phrase, as in Postgres.
+#[derive(Debug, Default, PartialEq)]
+pub struct SearchTerms {
+    /// Every group needs one of its terms in the quote.
+    pub required: Vec<Vec<String>>,
+    pub excluded: Vec<String>
+}
+
+impl SearchTerms {
+    pub fn parse(q: &str) -> Self {
+        let mut terms = Self::default();
+        let mut chars = q.chars().peekable();
+        let mut alternative = false;
+
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+            let Some(first) = chars.next() else {
+                break
+            };
+
+            let excluded = first == '-';
+            let quoted = if excluded { chars.next_if_eq(&'"').is_some() } else { first == '"' };
+            let mut term = match excluded || quoted {
+                true => String::new(),
+                false => first.to_string()
+            };
+
+            match quoted {
+                true => term.extend(chars.by_ref().take_while(|c| *c != '"')),
+                false => while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    term.push(c)
+                }
+            }
+
+            let term = term.trim().to_lowercase();
+
+            if term.is_empty() {
+                continue
+            }
+
+            if !quoted && !excluded && term == "or" {
+                alternative = !terms.required.is_empty();
+                continue
+            }
+
+            match (excluded, terms.required.last_mut()) {
+                (true, _) => terms.excluded.push(term),
+                (false, Some(group)) if alternative => group.push(term),
+                (false, _) => terms.required.push(vec![term])
+            }
+
+            alternative = false;
+        }
+
+        terms
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+
+        self.required.iter().all(|group| group.iter().any(|term| text.contains(term.as_str())))
+            && !self.excluded.iter().any(|term| text.contains(term.as_str()))
+    }
+
+    /// The escaped text with every occurrence of a required term wrapped in
+    /// `<mark>`, like the Postgres store's highlights.
+    pub fn highlight(&self, text: &str) -> String {
+        let (folded, offsets) = fold(text);
+
+        let mut ranges = self.required.iter()
+            .flatten()
+            .flat_map(|term| folded.match_indices(term.as_str()).map(|(start, term)| (start, start + term.len())))
+            .collect::<Vec<_>>();
+
+        ranges.sort();
+
+        let mut highlight = String::with_capacity(text.len());
+        let mut copied = 0;
+
+        for (start, end) in ranges {
+            let start = offsets[start].max(copied);
+            let end = original_end(text, &offsets, end);
+
+            if start >= end {
+                continue
+            }
+
+            highlight.push_str(&encode_text(&text[copied..start]));
+            highlight.push_str("<mark>");
+            highlight.push_str(&encode_text(&text[start..end]));
+            highlight.push_str("</mark>");
+            copied = end;
+        }
+
+        highlight.push_str(&encode_text(&text[copied..]));
+
+        highlight
+    }
+}
+
+/// Lowercases `text`, along with the offset in `text` of the character each
+/// byte of the result comes from.
+fn fold(text: &str) -> (String, Vec<usize>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len());
+
+    for (offset, c) in text.char_indices() {
+        folded.extend(c.to_lowercase());
+        offsets.resize(folded.len(), offset);
+    }
+
+    (folded, offsets)
+}
+
+/// Where a match ending at `end` in the folded text ends in `text`, taking
+/// in the whole character when lowercasing expanded it.
+fn original_end(text: &str, offsets: &[usize], end: usize) -> usize {
+    let last = offsets[end - 1];
+
+    last + text[last..].chars().next().map_or(0, char::len_utf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(page: i64, per_page: i64) -> QuoteSearch {
+        QuoteSearch {
+            q: None,
+            author: None,
+            since: None,
+            until: None,
+            page: Some(page),
+            per_page: Some(per_page)
+        }
+    }
+
+    #[test]
+    fn rejects_pages_whose_offset_overflows() {
+        assert_eq!(search(i64::MAX, MAX_PER_PAGE).validate().unwrap_err(), StatusCode::BAD_REQUEST);
+        assert_eq!(search(3, 10).validate().unwrap().offset(), 20);
+    }
+
+    #[test]
+    fn parses_web_search_syntax() {
+        let terms = SearchTerms::parse(r#"Santa "north pole" or sleigh -grinch -"bah humbug""#);
+
+        assert_eq!(terms.required, vec![vec!["santa".to_string()], vec!["north pole".to_string(), "sleigh".to_string()]]);
+        assert_eq!(terms.excluded, vec!["grinch".to_string(), "bah humbug".to_string()]);
+        assert!(terms.matches("SANTA rides his sleigh"));
+        assert!(!terms.matches("Santa stays home"));
+        assert!(!terms.matches("Santa's sleigh, said the Grinch"));
+    }
+
+    #[test]
+    fn highlights_matches_in_the_original_text() {
+        let terms = SearchTerms::parse("straße ho");
+
+        assert_eq!(
+            terms.highlight("STRAßE: Ho ho <b>"),
+            "<mark>STRAßE</mark>: <mark>Ho</mark> <mark>ho</mark> &lt;b&gt;"
+        );
+        assert_eq!(SearchTerms::parse("İ").highlight("xİx"), "x<mark>İ</mark>x");
+    }
+
+    #[test]
+    fn escapes_highlights() {
+        assert_eq!(
+            SearchTerms::parse("mark").highlight("<mark>x</mark> & <script>"),
+            "&lt;<mark>mark</mark>&gt;x&lt;/<mark>mark</mark>&gt; &amp; &lt;script&gt;"
+        );
+        assert_eq!(SearchTerms::parse("a&b").highlight("a&b"), "<mark>a&amp;b</mark>");
+    }
+}
@@ -0,0 +1,849 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::{stream::BoxStream, TryStreamExt};
+use sqlx::{Error, Sqlite, SqliteExecutor, SqlitePool, Transaction};
+use uuid::Uuid;
+
+use super::{
+    collections::{
+        Collection, CollectionForCreation, CollectionForUpdate, CollectionOutcome,
+        CollectionSummary, CollectionWithQuotes
+    },
+    quote_repository::{QuoteRepository, UpdateOutcome},
+    search::{QuoteSearch, SearchHit, SearchResults, SearchTerms},
+    transfer::{ImportError, QuoteForImport},
+    Quote, QuoteForCreation, QuoteForUpdate, QuoteHistory, QuoteVersion
+};
+
+/// Quotes in a single SQLite file, along with their history, tags and
+/// collections. There's no full text search: the words and phrases of `q` are
+/// looked for as they are, so search results have no rank.
+pub struct SqliteQuoteRepository {
+    pool: SqlitePool
+}
+
+impl SqliteQuoteRepository {
+    pub fn build(pool: SqlitePool) -> Self {
+        Self {
+            pool
+        }
+    }
+}
+
+#[async_trait]
+impl QuoteRepository for SqliteQuoteRepository {
+    async fn create_quote(
+        &self,
+        quote_for_creation: QuoteForCreation,
+        owner: Option<&str>
+    ) -> Result<Quote, Error> {
+        sqlx::query_as::<_, Quote>(
+            "INSERT INTO quotes (id, author, quote, created_at, version, owner, changed_by)
+            VALUES ($1, $2, $3, $4, 1, $5, $5)
+            RETURNING *;"
+        )
+        .bind(Uuid::new_v4())
+        .bind(&quote_for_creation.author)
+        .bind(&quote_for_creation.quote)
+        .bind(Utc::now())
+        .bind(owner)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn get_quote(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Quote>, Error> {
+        sqlx::query_as::<_, Quote>(
+            "SELECT * FROM quotes WHERE id = $1 AND deleted_at IS NULL;"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn find_quote(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Quote>, Error> {
+        sqlx::query_as::<_, Quote>("SELECT * FROM quotes WHERE id = $1;")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn delete_quote(
+        &self,
+        id: &Uuid,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(version) = archive_quote(&mut tx, id, None, "delete").await? else {
+            return Ok(None)
+        };
+
+        let quote = sqlx::query_as::<_, Quote>(
+            "UPDATE quotes SET deleted_at = $1, changed_by = $3, version = $4
+            WHERE id = $2
+            RETURNING *;"
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .bind(changed_by)
+        .bind(version + 1)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(quote))
+    }
+
+    async fn restore_quote(
+        &self,
+        id: &Uuid,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error> {
+        sqlx::query_as::<_, Quote>(
+            "UPDATE quotes SET deleted_at = NULL, changed_by = $2
+            WHERE id = $1 AND deleted_at IS NOT NULL
+            RETURNING *;"
+        )
+        .bind(id)
+        .bind(changed_by)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn list_trash(&self) -> Result<Vec<Quote>, Error> {
+        sqlx::query_as::<_, Quote>(
+            "SELECT * FROM quotes WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC, id;"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn update_quote(
+        &self,
+        id: &Uuid,
+        quote_for_update: &QuoteForUpdate,
+        expected_version: Option<i32>,
+        changed_by: &str
+    ) -> Result<UpdateOutcome, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(version) = archive_quote(&mut tx, id, expected_version, "update").await? else {
+            let current_version = sqlx::query_scalar::<_, i32>(
+                "SELECT version FROM quotes WHERE id = $1 AND deleted_at IS NULL;"
+            )
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            return Ok(match current_version {
+                Some(current_version) => UpdateOutcome::Conflict(current_version),
+                None => UpdateOutcome::NotFound
+            })
+        };
+
+        let quote = sqlx::query_as::<_, Quote>(
+            "UPDATE quotes
+            SET author = $1, quote = $2, version = $3, changed_by = $6
+            WHERE id = $4 AND version = $5
+            RETURNING *;"
+        )
+        .bind(&quote_for_update.author)
+        .bind(&quote_for_update.quote)
+        .bind(version + 1)
+        .bind(id)
+        .bind(version)
+        .bind(changed_by)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(UpdateOutcome::Updated(quote))
+    }
+
+    /// The archiving insert takes the write lock, so no quote can be created
+    /// before the update trashes the ones it archived.
+    async fn clean_db(&self, changed_by: Option<&str>) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        let now = Utc::now();
+
+        sqlx::query(
+            "INSERT INTO quote_versions (quote_id, version, author, quote, created_at, superseded_at, superseded_by, changed_by)
+            SELECT id, version, author, quote, created_at, $1, 'reset', changed_by FROM quotes
+            WHERE deleted_at IS NULL;"
+        )
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query(
+            "UPDATE quotes SET deleted_at = $1, changed_by = $2, version = version + 1
+            WHERE deleted_at IS NULL;"
+        )
+        .bind(now)
+        .bind(changed_by)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Times are stored as RFC 3339 text in UTC, which sorts chronologically.
+    /// Tags and places in collections go along through their foreign keys,
+    /// which sqlx turns on for every connection.
+    async fn purge_deleted(
+        &self,
+        deleted_before: &DateTime<Utc>
+    ) -> Result<u64, Error> {
+        let result = sqlx::query("DELETE FROM quotes WHERE deleted_at < $1;")
+            .bind(deleted_before)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn get_history(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<QuoteHistory>, Error> {
+        let current = sqlx::query_as::<_, Quote>("SELECT * FROM quotes WHERE id = $1;")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let versions = sqlx::query_as::<_, QuoteVersion>(
+            "SELECT * FROM quote_versions WHERE quote_id = $1 ORDER BY version;"
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if current.is_none() && versions.is_empty() {
+            return Ok(None)
+        }
+
+        Ok(Some(QuoteHistory {
+            current,
+            versions
+        }))
+    }
+
+    /// Brings the quote back before looking up the version, so the
+    /// transaction holds the write lock from its first statement on.
+    async fn revert_quote(
+        &self,
+        id: &Uuid,
+        version: i32,
+        changed_by: &str
+    ) -> Result<Option<Quote>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE quotes SET deleted_at = NULL WHERE id = $1;")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        let Some(past) = sqlx::query_as::<_, QuoteVersion>(
+            "SELECT * FROM quote_versions WHERE quote_id = $1 AND version = $2;"
+        )
+        .bind(id)
+        .bind(version)
+        .fetch_optional(&mut *tx)
+        .await? else {
+            return Ok(None)
+        };
+
+        let quote = match archive_quote(&mut tx, id, None, "revert").await? {
+            Some(current_version) => sqlx::query_as::<_, Quote>(
+                "UPDATE quotes
+                SET author = $1, quote = $2, version = $3, changed_by = $6
+                WHERE id = $4 AND version = $5
+                RETURNING *;"
+            )
+            .bind(&past.author)
+            .bind(&past.quote)
+            .bind(current_version + 1)
+            .bind(id)
+            .bind(current_version)
+            .bind(changed_by)
+            .fetch_optional(&mut *tx)
+            .await?,
+            None => {
+                let latest_version = sqlx::query_scalar::<_, i32>(
+                    "SELECT MAX(version) FROM quote_versions WHERE quote_id = $1;"
+                )
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?;
+
+                Some(sqlx::query_as::<_, Quote>(
+                    "INSERT INTO quotes (id, author, quote, created_at, version, changed_by)
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    RETURNING *;"
+                )
+                .bind(id)
+                .bind(&past.author)
+                .bind(&past.quote)
+                .bind(past.created_at)
+                .bind(latest_version + 1)
+                .bind(changed_by)
+                .fetch_one(&mut *tx)
+                .await?)
+            }
+        };
+
+        tx.commit().await?;
+
+        Ok(quote)
+    }
+
+    fn export_quotes(&self) -> BoxStream<'static, Result<Quote, Error>> {
+        let pool = self.pool.clone();
+
+        Box::pin(async_stream::try_stream! {
+            let mut quotes = sqlx::query_as::<_, Quote>(
+                "SELECT * FROM quotes WHERE deleted_at IS NULL ORDER BY created_at, id;"
+            )
+            .fetch(&pool);
+
+            while let Some(quote) = quotes.try_next().await? {
+                yield quote;
+            }
+        })
+    }
+
+    async fn import_quotes(
+        &self,
+        quotes: &[(u64, QuoteForImport)],
+        owner: &str,
+        dry_run: bool
+    ) -> Result<Vec<ImportError>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut errors = Vec::new();
+
+        for (line, quote) in quotes {
+            let id = quote.id.unwrap_or_else(Uuid::new_v4);
+
+            let inserted = sqlx::query_scalar::<_, Uuid>(
+                "INSERT INTO quotes (id, author, quote, created_at, version, owner, changed_by)
+                SELECT $1, $2, $3, $4, 1, $5, $5
+                WHERE NOT EXISTS (SELECT 1 FROM quote_versions WHERE quote_id = $1)
+                ON CONFLICT (id) DO NOTHING
+                RETURNING id;"
+            )
+            .bind(id)
+            .bind(&quote.author)
+            .bind(&quote.quote)
+            .bind(quote.created_at.unwrap_or_else(Utc::now))
+            .bind(owner)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if inserted.is_none() {
+                errors.push(ImportError::new(*line, format!("Quote {} already exists or was purged", id)));
+            }
+        }
+
+        if dry_run || !errors.is_empty() {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+
+        Ok(errors)
+    }
+
+    async fn pick_quote(
+        &self,
+        fraction: f64,
+        author: Option<&str>,
+        created_before: Option<&DateTime<Utc>>
+    ) -> Result<Option<Quote>, Error> {
+        sqlx::query_as::<_, Quote>(
+            "SELECT * FROM quotes
+            WHERE deleted_at IS NULL
+            AND ($2 IS NULL OR lower(author) = lower($2))
+            AND ($3 IS NULL OR created_at < $3)
+            ORDER BY created_at, id
+            LIMIT 1
+            OFFSET CAST($1 * (
+                SELECT COUNT(*) FROM quotes
+                WHERE deleted_at IS NULL
+                AND ($2 IS NULL OR lower(author) = lower($2))
+                AND ($3 IS NULL OR created_at < $3)
+            ) AS INTEGER);"
+        )
+        .bind(fraction)
+        .bind(author)
+        .bind(created_before)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn tag_quote(
+        &self,
+        id: &Uuid,
+        tag: &str
+    ) -> Result<Option<Vec<String>>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        if !is_live(&mut *tx, id).await? {
+            return Ok(None)
+        }
+
+        let now = Utc::now();
+
+        sqlx::query("INSERT INTO tags (name, created_at) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING;")
+            .bind(tag)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO quote_tags (quote_id, tag, tagged_at) VALUES ($1, $2, $3)
+            ON CONFLICT (quote_id, tag) DO NOTHING;"
+        )
+        .bind(id)
+        .bind(tag)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        let tags = quote_tags(&mut *tx, id).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(tags))
+    }
+
+    async fn untag_quote(
+        &self,
+        id: &Uuid,
+        tag: &str
+    ) -> Result<Option<Vec<String>>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        if !is_live(&mut *tx, id).await? {
+            return Ok(None)
+        }
+
+        sqlx::query("DELETE FROM quote_tags WHERE quote_id = $1 AND tag = $2;")
+            .bind(id)
+            .bind(tag)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "DELETE FROM tags WHERE name = $1
+            AND NOT EXISTS (SELECT 1 FROM quote_tags WHERE tag = $1);"
+        )
+        .bind(tag)
+        .execute(&mut *tx)
+        .await?;
+
+        let tags = quote_tags(&mut *tx, id).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(tags))
+    }
+
+    async fn get_tags(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Vec<String>>, Error> {
+        if !is_live(&self.pool, id).await? {
+            return Ok(None)
+        }
+
+        Ok(Some(quote_tags(&self.pool, id).await?))
+    }
+
+    async fn list_tagged(
+        &self,
+        tag: &str
+    ) -> Result<Vec<Quote>, Error> {
+        sqlx::query_as::<_, Quote>(
+            "SELECT quotes.* FROM quotes
+            JOIN quote_tags ON quote_tags.quote_id = quotes.id
+            WHERE quote_tags.tag = $1 AND quotes.deleted_at IS NULL
+            ORDER BY quotes.created_at, quotes.id;"
+        )
+        .bind(tag)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn create_collection(
+        &self,
+        collection_for_creation: &CollectionForCreation,
+        owner: &str
+    ) -> Result<CollectionOutcome, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let collection = sqlx::query_as::<_, Collection>(
+            "INSERT INTO collections (id, name, created_at, owner)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *;"
+        )
+        .bind(Uuid::new_v4())
+        .bind(&collection_for_creation.name)
+        .bind(Utc::now())
+        .bind(owner)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let unknown = set_collection_quotes(&mut tx, &collection.id, &collection_for_creation.quotes).await?;
+        if !unknown.is_empty() {
+            return Ok(CollectionOutcome::UnknownQuotes(unknown))
+        }
+
+        let quotes = collection_quotes(&mut *tx, &collection.id).await?;
+
+        tx.commit().await?;
+
+        Ok(CollectionOutcome::Saved(CollectionWithQuotes {
+            collection,
+            quotes
+        }))
+    }
+
+    async fn list_collections(&self) -> Result<Vec<CollectionSummary>, Error> {
+        sqlx::query_as::<_, CollectionSummary>(
+            "SELECT collections.*, COUNT(collection_quotes.quote_id) AS size
+            FROM collections
+            LEFT JOIN collection_quotes ON collection_quotes.collection_id = collections.id
+            GROUP BY collections.id
+            ORDER BY collections.name;"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn get_collection(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<CollectionWithQuotes>, Error> {
+        let Some(collection) = sqlx::query_as::<_, Collection>(
+            "SELECT * FROM collections WHERE id = $1;"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await? else {
+            return Ok(None)
+        };
+
+        let quotes = collection_quotes(&self.pool, id).await?;
+
+        Ok(Some(CollectionWithQuotes {
+            collection,
+            quotes
+        }))
+    }
+
+    async fn update_collection(
+        &self,
+        id: &Uuid,
+        collection_for_update: &CollectionForUpdate
+    ) -> Result<CollectionOutcome, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let collection = sqlx::query_as::<_, Collection>(
+            "UPDATE collections SET name = COALESCE($1, name)
+            WHERE id = $2
+            RETURNING *;"
+        )
+        .bind(&collection_for_update.name)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(collection) = collection else {
+            return Ok(CollectionOutcome::NotFound)
+        };
+
+        if let Some(quotes) = &collection_for_update.quotes {
+            sqlx::query("DELETE FROM collection_quotes WHERE collection_id = $1;")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+
+            let unknown = set_collection_quotes(&mut tx, id, quotes).await?;
+            if !unknown.is_empty() {
+                return Ok(CollectionOutcome::UnknownQuotes(unknown))
+            }
+        }
+
+        let quotes = collection_quotes(&mut *tx, id).await?;
+
+        tx.commit().await?;
+
+        Ok(CollectionOutcome::Saved(CollectionWithQuotes {
+            collection,
+            quotes
+        }))
+    }
+
+    async fn delete_collection(
+        &self,
+        id: &Uuid
+    ) -> Result<Option<Collection>, Error> {
+        sqlx::query_as::<_, Collection>(
+            "DELETE FROM collections WHERE id = $1 RETURNING *;"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Newest first, as there's no rank to order by.
+    async fn search_quotes(
+        &self,
+        search: &QuoteSearch
+    ) -> Result<SearchResults, Error> {
+        let terms = search.q.as_deref().map(SearchTerms::parse);
+        let (filter, binds) = search_filter(terms.as_ref());
+        let page_sql = format!(
+            "SELECT * FROM quotes {} ORDER BY created_at DESC, id LIMIT ${} OFFSET ${};",
+            filter,
+            binds.len() + 4,
+            binds.len() + 5
+        );
+        let count_sql = format!("SELECT COUNT(*) FROM quotes {};", filter);
+
+        let mut page = sqlx::query_as::<_, Quote>(&page_sql)
+            .bind(&search.author)
+            .bind(search.since)
+            .bind(search.until);
+        let mut count = sqlx::query_scalar::<_, i64>(&count_sql)
+            .bind(&search.author)
+            .bind(search.since)
+            .bind(search.until);
+
+        for term in &binds {
+            page = page.bind(term);
+            count = count.bind(term);
+        }
+
+        let results = page
+            .bind(search.per_page())
+            .bind(search.offset())
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|quote| SearchHit {
+                highlight: terms.as_ref().map(|terms| terms.highlight(&quote.quote)),
+                rank: None,
+                quote
+            })
+            .collect();
+
+        let total = count.fetch_one(&self.pool).await?;
+
+        let next_page = (search.offset() + search.per_page() < total)
+            .then(|| search.page() + 1);
+
+        Ok(SearchResults {
+            results,
+            page: search.page(),
+            per_page: search.per_page(),
+            total,
+            next_page
+        })
+    }
+}
+
+/// Copies the current row of a quote into its history before it gets
+/// overwritten, returning the archived version. SQLite has a single writer,
+/// and this being the first write of the transaction, concurrent writers wait
+/// for it and then see the version it left. `None` when the quote doesn't
+/// exist, is trashed or isn't at `expected_version`.
+async fn archive_quote(
+    tx: &mut Transaction<'_, Sqlite>,
+    id: &Uuid,
+    expected_version: Option<i32>,
+    superseded_by: &str
+) -> Result<Option<i32>, Error> {
+    sqlx::query_scalar::<_, i32>(
+        "INSERT INTO quote_versions (quote_id, version, author, quote, created_at, superseded_at, superseded_by, changed_by)
+        SELECT id, version, author, quote, created_at, $3, $4, changed_by FROM quotes
+        WHERE id = $1 AND ($2 IS NULL OR version = $2) AND deleted_at IS NULL
+        RETURNING version;"
+    )
+    .bind(id)
+    .bind(expected_version)
+    .bind(Utc::now())
+    .bind(superseded_by)
+    .fetch_optional(&mut **tx)
+    .await
+}
+
+/// The filter of `search_quotes`, with a condition per term of `q` bound from
+/// `$4` on, in the order of the returned terms. `instr` rather than `LIKE`, so
+/// terms need no escaping. `lower` only folds ASCII letters.
+fn search_filter(terms: Option<&SearchTerms>) -> (String, Vec<&String>) {
+    let mut filter = String::from(
+        "WHERE ($1 IS NULL OR lower(author) = lower($1))
+        AND ($2 IS NULL OR created_at >= $2)
+        AND ($3 IS NULL OR created_at <= $3)
+        AND deleted_at IS NULL"
+    );
+    let mut binds = Vec::new();
+
+    let Some(terms) = terms else {
+        return (filter, binds)
+    };
+
+    for group in &terms.required {
+        let alternatives = group.iter()
+            .map(|term| {
+                binds.push(term);
+                format!("instr(lower(quote), ${}) > 0", binds.len() + 3)
+            })
+            .collect::<Vec<_>>();
+
+        filter.push_str(&format!(" AND ({})", alternatives.join(" OR ")));
+    }
+
+    for term in &terms.excluded {
+        binds.push(term);
+        filter.push_str(&format!(" AND instr(lower(quote), ${}) = 0", binds.len() + 3));
+    }
+
+    (filter, binds)
+}
+
+async fn is_live(
+    executor: impl SqliteExecutor<'_>,
+    id: &Uuid
+) -> Result<bool, Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM quotes WHERE id = $1 AND deleted_at IS NULL);"
+    )
+    .bind(id)
+    .fetch_one(executor)
+    .await
+}
+
+async fn quote_tags(
+    executor: impl SqliteExecutor<'_>,
+    id: &Uuid
+) -> Result<Vec<String>, Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT tag FROM quote_tags WHERE quote_id = $1 ORDER BY tag;"
+    )
+    .bind(id)
+    .fetch_all(executor)
+    .await
+}
+
+/// Live quotes of a collection, in the collection's order.
+async fn collection_quotes(
+    executor: impl SqliteExecutor<'_>,
+    collection_id: &Uuid
+) -> Result<Vec<Quote>, Error> {
+    sqlx::query_as::<_, Quote>(
+        "SELECT quotes.* FROM quotes
+        JOIN collection_quotes ON collection_quotes.quote_id = quotes.id
+        WHERE collection_quotes.collection_id = $1 AND quotes.deleted_at IS NULL
+        ORDER BY collection_quotes.position;"
+    )
+    .bind(collection_id)
+    .fetch_all(executor)
+    .await
+}
+
+/// Adds quotes to an empty collection in the given order, returning the ids
+/// that don't match a live quote. The caller must not commit if any did.
+async fn set_collection_quotes(
+    tx: &mut Transaction<'_, Sqlite>,
+    collection_id: &Uuid,
+    quotes: &[Uuid]
+) -> Result<Vec<Uuid>, Error> {
+    let mut unknown = Vec::new();
+
+    for (position, quote_id) in (0..).zip(quotes) {
+        let added = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO collection_quotes (collection_id, quote_id, position)
+            SELECT $1, id, $3 FROM quotes WHERE id = $2 AND deleted_at IS NULL
+            RETURNING quote_id;"
+        )
+        .bind(collection_id)
+        .bind(quote_id)
+        .bind(position)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        if added.is_none() {
+            unknown.push(*quote_id);
+        }
+    }
+
+    Ok(unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::SqliteQuoteRepository;
+    use crate::routes::{
+        day_nineteen::quote_repository::tests as suite,
+        schema::schema_controller::SQLITE_MIGRATOR
+    };
+
+    /// Every connection to `sqlite::memory:` opens a database of its own.
+    async fn repository() -> SqliteQuoteRepository {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        SQLITE_MIGRATOR.run(&pool).await.unwrap();
+
+        SqliteQuoteRepository::build(pool)
+    }
+
+    #[tokio::test]
+    async fn keeps_history_and_reverts() {
+        suite::keeps_history_and_reverts(Arc::new(repository().await)).await
+    }
+
+    #[tokio::test]
+    async fn purges_tags_and_collection_places() {
+        suite::purges_tags_and_collection_places(Arc::new(repository().await)).await
+    }
+
+    #[tokio::test]
+    async fn refuses_taken_collection_names() {
+        suite::refuses_taken_collection_names(Arc::new(repository().await)).await
+    }
+
+    #[tokio::test]
+    async fn rolls_back_imports_with_clashes() {
+        suite::rolls_back_imports_with_clashes(Arc::new(repository().await)).await
+    }
+
+    #[tokio::test]
+    async fn searches_newest_first_with_highlights() {
+        suite::searches_newest_first_with_highlights(Arc::new(repository().await)).await
+    }
+
+    #[tokio::test]
+    async fn picks_quotes_by_position() {
+        suite::picks_quotes_by_position(Arc::new(repository().await)).await
+    }
+}
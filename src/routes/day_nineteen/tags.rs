@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use axum::{extract::{Path, State}, http::{HeaderMap, StatusCode}, Json};
+use uuid::Uuid;
+
+use super::{auth::authorize_change, error::QuoteError, validation::validate_tag, Quote};
+use crate::AppState;
+
+#[axum::debug_handler]
+pub async fn list_tags(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>
+) -> Result<Json<Vec<String>>, QuoteError> {
+    let tags = state.quote_repository
+        .get_tags(&id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(tags))
+}
+
+/// Owner or admin only, like every other change to a quote.
+#[axum::debug_handler]
+pub async fn tag(
+    State(state): State<Arc<AppState>>,
+    Path((id, tag)): Path<(Uuid, String)>,
+    headers: HeaderMap
+) -> Result<Json<Vec<String>>, QuoteError> {
+    authorize_change(&state, &headers, &id).await?;
+
+    let tag = validate_tag(&tag)?;
+
+    let tags = state.quote_repository
+        .tag_quote(&id, &tag)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(tags))
+}
+
+/// Owner or admin only.
+#[axum::debug_handler]
+pub async fn untag(
+    State(state): State<Arc<AppState>>,
+    Path((id, tag)): Path<(Uuid, String)>,
+    headers: HeaderMap
+) -> Result<Json<Vec<String>>, QuoteError> {
+    authorize_change(&state, &headers, &id).await?;
+
+    let tag = validate_tag(&tag)?;
+
+    let tags = state.quote_repository
+        .untag_quote(&id, &tag)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(tags))
+}
+
+#[axum::debug_handler]
+pub async fn tagged(
+    State(state): State<Arc<AppState>>,
+    Path(tag): Path<String>
+) -> Result<Json<Vec<Quote>>, QuoteError> {
+    let tag = validate_tag(&tag)?;
+
+    let quotes = state.quote_repository
+        .list_tagged(&tag)
+        .await?;
+
+    Ok(Json(quotes))
+}
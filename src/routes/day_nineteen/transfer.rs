@@ -0,0 +1,225 @@
+use chrono::{DateTime, Utc};
+use csv::{ReaderBuilder, Trim, WriterBuilder};
+use mime::Mime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{config::QuoteLimits, validation::validate_quote, Quote};
+
+const CSV_HEADER: &[u8] = b"id,author,quote,created_at,version\n";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferFormat {
+    JsonLines,
+    Csv
+}
+
+/// One line of an import. Exports can be imported back as is: ids and
+/// creation times are kept when present, versions start over at 1.
+#[derive(Deserialize, Debug)]
+pub struct QuoteForImport {
+    pub id: Option<Uuid>,
+    pub author: String,
+    pub quote: String,
+    pub created_at: Option<DateTime<Utc>>
+}
+
+/// A line number and what was found on that line.
+pub type ImportLine = (u64, Result<QuoteForImport, String>);
+
+#[derive(Serialize, Debug)]
+struct QuoteRecord<'a> {
+    id: &'a Uuid,
+    author: &'a str,
+    quote: &'a str,
+    created_at: &'a DateTime<Utc>,
+    version: i32
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportError {
+    pub line: u64,
+    pub message: String
+}
+
+impl ImportError {
+    pub fn new(line: u64, message: String) -> Self {
+        Self { line, message }
+    }
+}
+
+/// Nothing is imported unless every line is valid. `valid` counts the lines
+/// that would have been imported, `imported` the ones that were.
+#[derive(Serialize, Debug)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub valid: usize,
+    pub imported: usize,
+    pub errors: Vec<ImportError>
+}
+
+impl TransferFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            TransferFormat::JsonLines => "application/jsonl",
+            TransferFormat::Csv => "text/csv; charset=utf-8"
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            TransferFormat::JsonLines => "quotes.jsonl",
+            TransferFormat::Csv => "quotes.csv"
+        }
+    }
+
+    /// Picks the preferred format of an Accept header, by q-value then order.
+    /// JSON Lines when there's no header or anything goes, `None` when
+    /// nothing acceptable is supported.
+    pub fn from_accept(accept: Option<&str>) -> Option<Self> {
+        let Some(accept) = accept.filter(|accept| !accept.trim().is_empty()) else {
+            return Some(TransferFormat::JsonLines)
+        };
+
+        let mut ranges = accept.split(',')
+            .filter_map(|range| range.trim().parse::<Mime>().ok())
+            .map(|mime| {
+                let quality = mime.get_param("q")
+                    .and_then(|q| q.as_str().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (mime, quality)
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect::<Vec<_>>();
+
+        ranges.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        ranges.iter().find_map(|(mime, _)| match (mime.type_(), mime.subtype()) {
+            (mime::STAR, mime::STAR) => Some(TransferFormat::JsonLines),
+            (mime::APPLICATION, mime::STAR) => Some(TransferFormat::JsonLines),
+            (mime::TEXT, mime::STAR) => Some(TransferFormat::Csv),
+            _ => Self::from_mime(mime)
+        })
+    }
+
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        Self::from_mime(&content_type.trim().parse().ok()?)
+    }
+
+    /// For imports sent without a Content-Type: JSON Lines start with an
+    /// object, CSV with its header row.
+    pub fn sniff(body: &str) -> Self {
+        match body.trim_start().starts_with('{') {
+            true => TransferFormat::JsonLines,
+            false => TransferFormat::Csv
+        }
+    }
+
+    fn from_mime(mime: &Mime) -> Option<Self> {
+        match (mime.type_().as_str(), mime.subtype().as_str()) {
+            ("application", "jsonl" | "x-jsonl" | "jsonlines" | "x-jsonlines" | "x-ndjson" | "ndjson") => {
+                Some(TransferFormat::JsonLines)
+            },
+            ("text", "csv") => Some(TransferFormat::Csv),
+            _ => None
+        }
+    }
+
+    /// What goes before the first quote of an export.
+    pub fn preamble(&self) -> &'static [u8] {
+        match self {
+            TransferFormat::JsonLines => b"",
+            TransferFormat::Csv => CSV_HEADER
+        }
+    }
+
+    pub fn encode(&self, quote: &Quote) -> Result<Vec<u8>, String> {
+        let record = QuoteRecord {
+            id: &quote.id,
+            author: &quote.author,
+            quote: &quote.quote,
+            created_at: &quote.created_at,
+            version: quote.version
+        };
+
+        match self {
+            TransferFormat::JsonLines => {
+                let mut line = serde_json::to_vec(&record).map_err(|e| e.to_string())?;
+                line.push(b'\n');
+                Ok(line)
+            },
+            TransferFormat::Csv => {
+                let mut writer = WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(Vec::new());
+
+                writer.serialize(&record).map_err(|e| e.to_string())?;
+                writer.into_inner().map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Parses every line of an import along with its line number, so errors
+    /// can be reported for all of them at once. Fails as a whole only when a
+    /// CSV header is unusable.
+    pub fn parse(
+        &self,
+        body: &str
+    ) -> Result<Vec<ImportLine>, String> {
+        match self {
+            TransferFormat::JsonLines => Ok(body.lines()
+                .zip(1..)
+                .filter(|(line, _)| !line.trim().is_empty())
+                .map(|(line, number)| (
+                    number,
+                    serde_json::from_str::<QuoteForImport>(line).map_err(|e| e.to_string())
+                ))
+                .collect()),
+            TransferFormat::Csv => {
+                let mut reader = ReaderBuilder::new()
+                    .trim(Trim::Headers)
+                    .from_reader(body.as_bytes());
+
+                let headers = reader.headers()
+                    .map_err(|e| format!("Invalid CSV header: {}", e))?
+                    .clone();
+
+                if !headers.iter().any(|header| header == "author")
+                    || !headers.iter().any(|header| header == "quote")
+                {
+                    return Err("CSV header must name author and quote columns".to_string())
+                }
+
+                Ok(reader.records()
+                    .map(|record| match record {
+                        Ok(record) => (
+                            record.position().map_or(0, |position| position.line()),
+                            record.deserialize::<QuoteForImport>(Some(&headers))
+                                .map_err(|e| e.to_string())
+                        ),
+                        Err(e) => (
+                            e.position().map_or(0, |position| position.line()),
+                            Err(e.to_string())
+                        )
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+impl QuoteForImport {
+    pub fn validate(self, limits: &QuoteLimits) -> Result<Self, String> {
+        let (author, quote) = validate_quote(&self.author, &self.quote, limits)
+            .map_err(|errors| errors.iter()
+                .map(|error| format!("{} {}", error.field, error.message))
+                .collect::<Vec<_>>()
+                .join(", "))?;
+
+        Ok(Self {
+            author,
+            quote,
+            ..self
+        })
+    }
+}
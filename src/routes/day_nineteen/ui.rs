@@ -0,0 +1,354 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        rejection::{FormRejection, QueryRejection},
+        Path, Query, State
+    },
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Form
+};
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{
+    auth::{authenticate_draft, authorize_change},
+    error::QuoteError,
+    quote_repository::UpdateOutcome,
+    search::{QuoteSearch, SearchHit},
+    validation::FieldError,
+    Quote, QuoteForCreation, QuoteForUpdate
+};
+use crate::AppState;
+
+/// Errors of the `/19/ui` fragments, rendered as HTML for the page to show
+/// in its error box.
+#[derive(Debug)]
+pub enum UiError {
+    Quote(QuoteError),
+    Message(StatusCode, &'static str)
+}
+
+impl From<QuoteError> for UiError {
+    fn from(error: QuoteError) -> Self {
+        UiError::Quote(error)
+    }
+}
+
+impl From<StatusCode> for UiError {
+    fn from(status: StatusCode) -> Self {
+        UiError::Quote(status.into())
+    }
+}
+
+impl From<sqlx::Error> for UiError {
+    fn from(error: sqlx::Error) -> Self {
+        UiError::Quote(error.into())
+    }
+}
+
+impl From<Vec<FieldError>> for UiError {
+    fn from(errors: Vec<FieldError>) -> Self {
+        UiError::Quote(errors.into())
+    }
+}
+
+impl IntoResponse for UiError {
+    fn into_response(self) -> Response {
+        let (status, message, fields) = match self {
+            UiError::Message(status, message) => (status, message.to_string(), Vec::new()),
+            UiError::Quote(QuoteError::Status(StatusCode::UNAUTHORIZED)) => (
+                StatusCode::UNAUTHORIZED,
+                "Paste a token issued by /19/tokens to make changes".to_string(),
+                Vec::new()
+            ),
+            UiError::Quote(error) => {
+                let status = error.status();
+
+                if let QuoteError::Database(e) = &error {
+                    println!("Quote database error: {}", e);
+                }
+
+                let message = error.message(status);
+                let fields = match error {
+                    QuoteError::Validation(fields) => fields,
+                    _ => Vec::new()
+                };
+
+                (status, message, fields)
+            }
+        };
+
+        let fields = fields.iter()
+            .map(|field| format!(
+                "<li>{}: {}</li>",
+                encode_text(field.field),
+                encode_text(&field.message)
+            ))
+            .collect::<String>();
+
+        let fragment = format!(
+            r#"<div class="error" role="alert"><p>{}</p>{}</div>"#,
+            encode_text(&message),
+            if fields.is_empty() { String::new() } else { format!("<ul>{}</ul>", fields) }
+        );
+
+        (status, Html(fragment)).into_response()
+    }
+}
+
+/// Only `q`, `author` and `page` are offered by the page.
+#[derive(Deserialize, Debug)]
+pub struct UiSearch {
+    pub q: Option<String>,
+    pub author: Option<String>,
+    pub page: Option<i64>
+}
+
+/// A quote as a list item with edit and delete buttons.
+fn quote_item(quote: &Quote, highlight: Option<&str>) -> String {
+    let id = quote.id.to_string();
+    let text = match highlight {
+        // Already escaped by the store
+        Some(highlight) => highlight.to_string(),
+        None => encode_text(&quote.quote).to_string()
+    };
+
+    let owner = quote.owner.as_deref()
+        .map(|owner| format!(" · {}", encode_text(owner)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<li class="quote" id="quote-{id}">
+    <blockquote>{text}</blockquote>
+    <p class="author">— {author} <span class="meta">v{version}{owner}</span></p>
+    <button hx-get="/19/ui/quotes/{id}/edit" hx-target="closest li" hx-swap="outerHTML">Edit</button>
+    <button hx-delete="/19/ui/quotes/{id}" hx-target="closest li" hx-swap="outerHTML" hx-confirm="Delete this quote?">Delete</button>
+</li>"#,
+        id = encode_double_quoted_attribute(&id),
+        text = text,
+        author = encode_text(&quote.author),
+        version = quote.version,
+        owner = owner
+    )
+}
+
+/// The edit form replaces the list item. The version goes along so a quote
+/// changed in the meantime isn't overwritten.
+fn edit_item(quote: &Quote) -> String {
+    let id = quote.id.to_string();
+
+    format!(
+        r#"<li class="quote editing" id="quote-{id}">
+    <form hx-put="/19/ui/quotes/{id}" hx-target="closest li" hx-swap="outerHTML">
+        <input type="hidden" name="version" value="{version}">
+        <input name="author" value="{author}" required>
+        <textarea name="quote" rows="3" required>{quote}</textarea>
+        <button type="submit">Save</button>
+        <button type="button" hx-get="/19/ui/quotes/{id}" hx-target="closest li" hx-swap="outerHTML">Cancel</button>
+    </form>
+</li>"#,
+        id = encode_double_quoted_attribute(&id),
+        version = quote.version,
+        author = encode_double_quoted_attribute(&quote.author),
+        quote = encode_text(&quote.quote)
+    )
+}
+
+/// One page of quotes, newest first or best match first when searching. The
+/// last item loads the next page in its place.
+#[axum::debug_handler]
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<UiSearch>, QueryRejection>
+) -> Result<Html<String>, UiError> {
+    let Query(query) = query.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+
+    let search = QuoteSearch {
+        q: query.q,
+        author: query.author,
+        since: None,
+        until: None,
+        page: query.page,
+        per_page: None
+    }.validate()?;
+
+    let results = state.quote_repository
+        .search_quotes(&search)
+        .await?;
+
+    let mut fragment = results.results
+        .iter()
+        .map(|SearchHit { quote, highlight, .. }| quote_item(quote, highlight.as_deref()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if results.total == 0 {
+        fragment.push_str(r#"<li class="empty">No quotes found</li>"#);
+    }
+
+    if let Some(next_page) = results.next_page {
+        let mut vals = json!({
+            "q": search.q,
+            "author": search.author,
+            "page": next_page
+        });
+
+        // htmx would send a null as the string "null"
+        if let Some(vals) = vals.as_object_mut() {
+            vals.retain(|_, value| !value.is_null());
+        }
+
+        fragment.push_str(&format!(
+            r#"
+<li class="more"><button hx-get="/19/ui/quotes" hx-vals="{}" hx-target="closest li" hx-swap="outerHTML">More</button></li>"#,
+            encode_double_quoted_attribute(&vals.to_string())
+        ));
+    }
+
+    Ok(Html(fragment))
+}
+
+#[axum::debug_handler]
+pub async fn show(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>
+) -> Result<Html<String>, UiError> {
+    let quote = state.quote_repository
+        .get_quote(&id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Html(quote_item(&quote, None)))
+}
+
+#[axum::debug_handler]
+pub async fn edit(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>
+) -> Result<Html<String>, UiError> {
+    let quote = state.quote_repository
+        .get_quote(&id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Html(edit_item(&quote)))
+}
+
+/// Answers with the new list item, owned by the caller like drafts.
+#[axum::debug_handler]
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    form: Result<Form<QuoteForCreation>, FormRejection>
+) -> Result<(StatusCode, Html<String>), UiError> {
+    let caller = authenticate_draft(&state, &headers).await?;
+
+    let Form(form) = form.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let form = form.validate(&state.quotes_config.limits)?;
+
+    let quote = state.quote_repository
+        .create_quote(form, caller.as_ref().map(|caller| caller.user.as_str()))
+        .await?;
+
+    Ok((StatusCode::CREATED, Html(quote_item(&quote, None))))
+}
+
+#[axum::debug_handler]
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    form: Result<Form<QuoteForUpdate>, FormRejection>
+) -> Result<Html<String>, UiError> {
+    let caller = authorize_change(&state, &headers, &id).await?;
+
+    let Form(form) = form.map_err(|e| QuoteError::InvalidBody(e.body_text()))?;
+    let form = form.validate(&state.quotes_config.limits)?;
+
+    match state.quote_repository
+        .update_quote(&id, &form, form.version, &caller.user)
+        .await?
+    {
+        UpdateOutcome::Updated(quote) => Ok(Html(quote_item(&quote, None))),
+        UpdateOutcome::NotFound => Err(StatusCode::NOT_FOUND.into()),
+        UpdateOutcome::Conflict(current_version) => {
+            println!("Quote {} is at version {}, expected {:?}", id, current_version, form.version);
+            Err(UiError::Message(
+                StatusCode::PRECONDITION_FAILED,
+                "Someone else changed this quote, cancel and edit it again"
+            ))
+        }
+    }
+}
+
+/// Answers with nothing, which removes the list item.
+#[axum::debug_handler]
+pub async fn delete(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap
+) -> Result<Html<String>, UiError> {
+    let caller = authorize_change(&state, &headers, &id).await?;
+
+    state.quote_repository
+        .delete_quote(&id, &caller.user)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Html(String::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::routes::day_nineteen::search::SearchTerms;
+
+    fn quote(author: &str, text: &str, owner: Option<&str>) -> Quote {
+        Quote {
+            id: Uuid::new_v4(),
+            author: author.to_string(),
+            quote: text.to_string(),
+            created_at: Utc::now(),
+            version: 1,
+            deleted_at: None,
+            owner: owner.map(str::to_string),
+            changed_by: None
+        }
+    }
+
+    #[test]
+    fn escapes_quote_items() {
+        let item = quote_item(&quote(r#"Grinch "the" <b>'s"#, "<script>alert(1)</script> & co", Some("<i>elf</i>")), None);
+
+        assert!(item.contains("<blockquote>&lt;script&gt;alert(1)&lt;/script&gt; &amp; co</blockquote>"));
+        assert!(item.contains(r#"— Grinch "the" &lt;b&gt;'s <span"#));
+        assert!(item.contains(" · &lt;i&gt;elf&lt;/i&gt;</span>"));
+        assert!(!item.contains("<script>") && !item.contains("<b>") && !item.contains("<i>"));
+    }
+
+    #[test]
+    fn keeps_only_highlight_marks() {
+        let quote = quote("Santa", "<mark>ho</mark> ho", None);
+        let highlight = SearchTerms::parse("ho").highlight(&quote.quote);
+        let item = quote_item(&quote, Some(&highlight));
+
+        assert!(item.contains(
+            "<blockquote>&lt;mark&gt;<mark>ho</mark>&lt;/mark&gt; <mark>ho</mark></blockquote>"
+        ));
+    }
+
+    #[test]
+    fn escapes_edit_items() {
+        let item = edit_item(&quote(r#""><script>alert(1)</script>"#, "</textarea><script>alert('x')</script>", None));
+
+        assert!(item.contains(r#"value="&quot;&gt;&lt;script&gt;alert(1)&lt;/script&gt;" required"#));
+        assert!(item.contains("required>&lt;/textarea&gt;&lt;script&gt;alert('x')&lt;/script&gt;</textarea>"));
+        assert!(!item.contains("<script>"));
+    }
+}
@@ -0,0 +1,122 @@
+use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
+
+use super::config::QuoteLimits;
+
+const MAX_TAG_LENGTH: usize = 50;
+const MAX_COLLECTION_NAME_LENGTH: usize = 100;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, message: String) -> Self {
+        Self { field, message }
+    }
+}
+
+/// Normalizes an author and a quote (NFC, trimmed, CRLF line endings as
+/// browsers submit textareas turned into LF) and checks them against the
+/// limits, reporting every problem at once. Lengths are in characters, after
+/// normalization.
+pub fn validate_quote(
+    author: &str,
+    quote: &str,
+    limits: &QuoteLimits
+) -> Result<(String, String), Vec<FieldError>> {
+    let author = normalize(author);
+    let quote = normalize(&quote.replace("\r\n", "\n"));
+    let mut errors = Vec::new();
+
+    check_length("author", &author, limits.max_author_length, &mut errors);
+    check_length("quote", &quote, limits.max_quote_length, &mut errors);
+
+    if author.chars().any(char::is_control) {
+        errors.push(FieldError::new("author", "can't contain control characters".to_string()));
+    }
+
+    if quote.chars().any(|c| c.is_control() && c != '\n' && c != '\t') {
+        errors.push(FieldError::new("quote", "can't contain control characters other than newlines and tabs".to_string()));
+    }
+
+    match errors.is_empty() {
+        true => Ok((author, quote)),
+        false => Err(errors)
+    }
+}
+
+/// Tags are case insensitive, so they are stored lowercased.
+pub fn validate_tag(tag: &str) -> Result<String, Vec<FieldError>> {
+    let tag = normalize(tag).to_lowercase();
+    let mut errors = Vec::new();
+
+    check_length("tag", &tag, MAX_TAG_LENGTH, &mut errors);
+
+    if tag.chars().any(|c| c.is_control() || c == '/') {
+        errors.push(FieldError::new("tag", "can't contain control characters or slashes".to_string()));
+    }
+
+    match errors.is_empty() {
+        true => Ok(tag),
+        false => Err(errors)
+    }
+}
+
+pub fn validate_collection_name(name: &str) -> Result<String, Vec<FieldError>> {
+    let name = normalize(name);
+    let mut errors = Vec::new();
+
+    check_length("name", &name, MAX_COLLECTION_NAME_LENGTH, &mut errors);
+
+    if name.chars().any(char::is_control) {
+        errors.push(FieldError::new("name", "can't contain control characters".to_string()));
+    }
+
+    match errors.is_empty() {
+        true => Ok(name),
+        false => Err(errors)
+    }
+}
+
+fn normalize(value: &str) -> String {
+    value.nfc().collect::<String>().trim().to_string()
+}
+
+fn check_length(
+    field: &'static str,
+    value: &str,
+    max_length: usize,
+    errors: &mut Vec<FieldError>
+) {
+    let length = value.chars().count();
+
+    if length == 0 {
+        errors.push(FieldError::new(field, "can't be empty".to_string()));
+    } else if length > max_length {
+        errors.push(FieldError::new(field, format!("can't be longer than {} characters", max_length)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_crlf_line_endings() {
+        let limits = QuoteLimits::default();
+        let (_, quote) = validate_quote("Someone", "First line\r\nSecond line\r\n", &limits).unwrap();
+
+        assert_eq!(quote, "First line\nSecond line");
+    }
+
+    #[test]
+    fn rejects_lone_carriage_returns() {
+        let limits = QuoteLimits::default();
+        let errors = validate_quote("Someone", "First line\rSecond line", &limits).unwrap_err();
+
+        assert_eq!(errors[0].field, "quote");
+    }
+}
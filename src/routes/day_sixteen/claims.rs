@@ -0,0 +1,260 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use axum::http::{HeaderMap, StatusCode};
+use jsonwebtoken::{Algorithm, Validation};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub gift: Value,
+    pub jti: Uuid,
+    pub exp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>
+}
+
+/// Audience of the bearer tokens of the /19 quote book. `wrap` won't issue
+/// tokens for it, they come from `/19/tokens`.
+pub const QUOTES_AUDIENCE: &str = "quotes";
+
+/// `[validation]` table of gift_tokens.toml. Times are in seconds.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ValidationSettings {
+    #[serde(default = "default_leeway")]
+    pub leeway: u64,
+    #[serde(default = "default_lifetime")]
+    pub default_lifetime: u64,
+    /// Longest lifetime a caller can ask for.
+    #[serde(default = "default_max_lifetime")]
+    pub max_lifetime: u64,
+    #[serde(default)]
+    pub audiences: Vec<String>,
+    #[serde(default)]
+    pub issuers: Vec<String>
+}
+
+fn default_leeway() -> u64 {
+    60
+}
+
+fn default_lifetime() -> u64 {
+    3600
+}
+
+fn default_max_lifetime() -> u64 {
+    24 * 3600
+}
+
+impl Default for ValidationSettings {
+    fn default() -> Self {
+        Self {
+            leeway: default_leeway(),
+            default_lifetime: default_lifetime(),
+            max_lifetime: default_max_lifetime(),
+            audiences: Vec::new(),
+            issuers: Vec::new()
+        }
+    }
+}
+
+/// Registered claims a caller can ask `wrap` for, via query parameters or
+/// the matching `X-Gift-*` headers (`X-Gift-Expires-In`, `X-Gift-Aud`, ...).
+/// Query parameters win when both are present.
+#[derive(Deserialize, Debug, Default)]
+pub struct ClaimOptions {
+    pub exp: Option<u64>,
+    pub expires_in: Option<u64>,
+    pub nbf: Option<u64>,
+    pub aud: Option<String>,
+    pub iss: Option<String>,
+    pub sub: Option<String>
+}
+
+impl ClaimOptions {
+    pub fn with_headers(self, headers: &HeaderMap) -> Result<Self, StatusCode> {
+        Ok(Self {
+            exp: self.exp.or(number_header(headers, "x-gift-exp")?),
+            expires_in: self.expires_in.or(number_header(headers, "x-gift-expires-in")?),
+            nbf: self.nbf.or(number_header(headers, "x-gift-nbf")?),
+            aud: self.aud.or(string_header(headers, "x-gift-aud")?),
+            iss: self.iss.or(string_header(headers, "x-gift-iss")?),
+            sub: self.sub.or(string_header(headers, "x-gift-sub")?)
+        })
+    }
+
+    pub fn into_claims(
+        self,
+        gift: Value,
+        settings: &ValidationSettings
+    ) -> Result<Claims, StatusCode> {
+        let now = now();
+
+        let exp = match (self.exp, self.expires_in) {
+            (Some(_), Some(_)) => {
+                println!("Only one of exp and expires_in can be requested");
+                return Err(StatusCode::BAD_REQUEST)
+            },
+            (Some(exp), None) => exp,
+            (None, expires_in) => now
+                .checked_add(expires_in.unwrap_or(settings.default_lifetime))
+                .ok_or_else(|| {
+                    println!("Requested lifetime is out of range");
+                    StatusCode::BAD_REQUEST
+                })?
+        };
+
+        if exp <= now {
+            println!("Requested expiry is in the past");
+            return Err(StatusCode::BAD_REQUEST)
+        }
+
+        if exp - now > settings.max_lifetime {
+            println!("Requested lifetime exceeds {} seconds", settings.max_lifetime);
+            return Err(StatusCode::BAD_REQUEST)
+        }
+
+        if self.nbf.is_some_and(|nbf| nbf >= exp) {
+            println!("Requested not-before is after the expiry");
+            return Err(StatusCode::BAD_REQUEST)
+        }
+
+        Ok(Claims {
+            gift,
+            jti: Uuid::new_v4(),
+            exp,
+            nbf: self.nbf,
+            aud: self.aud,
+            iss: self.iss,
+            sub: self.sub
+        })
+    }
+}
+
+/// What `unwrap` should expect of a token, via query parameters or
+/// `X-Gift-Aud`/`X-Gift-Iss`/`X-Gift-Sub`. Audiences and issuers fall back to
+/// the ones configured in gift_tokens.toml.
+#[derive(Deserialize, Debug, Default)]
+pub struct ClaimExpectations {
+    pub aud: Option<String>,
+    pub iss: Option<String>,
+    pub sub: Option<String>
+}
+
+impl ClaimExpectations {
+    pub fn with_headers(self, headers: &HeaderMap) -> Result<Self, StatusCode> {
+        Ok(Self {
+            aud: self.aud.or(string_header(headers, "x-gift-aud")?),
+            iss: self.iss.or(string_header(headers, "x-gift-iss")?),
+            sub: self.sub.or(string_header(headers, "x-gift-sub")?)
+        })
+    }
+
+    /// Signature and time based checks only, for when the caller just needs
+    /// to know the token is ours and still live (e.g. to revoke it).
+    pub fn any_audience(algorithm: Algorithm, settings: &ValidationSettings) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        validation.leeway = settings.leeway;
+        validation.validate_nbf = true;
+        validation.validate_aud = false;
+
+        validation
+    }
+
+    pub fn validation(
+        &self,
+        algorithm: Algorithm,
+        settings: &ValidationSettings
+    ) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        validation.leeway = settings.leeway;
+        validation.validate_nbf = true;
+
+        match &self.aud {
+            Some(aud) => validation.set_audience(&[aud]),
+            None if !settings.audiences.is_empty() => validation.set_audience(&settings.audiences),
+            None => ()
+        }
+
+        match &self.iss {
+            Some(iss) => validation.set_issuer(&[iss]),
+            None if !settings.issuers.is_empty() => validation.set_issuer(&settings.issuers),
+            None => ()
+        }
+
+        validation.sub = self.sub.clone();
+
+        validation
+    }
+
+    /// jsonwebtoken only checks `iss` when an issuer is expected, so a token
+    /// naming an issuer nobody asked for would otherwise pass. Rejects those,
+    /// the way unexpected audiences are.
+    pub fn accepts_issuer(&self, iss: Option<&str>, settings: &ValidationSettings) -> bool {
+        iss.is_none() || self.iss.is_some() || !settings.issuers.is_empty()
+    }
+}
+
+fn string_header(headers: &HeaderMap, name: &str) -> Result<Option<String>, StatusCode> {
+    headers.get(name)
+        .map(|value| value.to_str()
+            .map(|value| value.to_string())
+            .map_err(|e| {
+                println!("Invalid {} header: {}", name, e);
+                StatusCode::BAD_REQUEST
+            }))
+        .transpose()
+}
+
+fn number_header(headers: &HeaderMap, name: &str) -> Result<Option<u64>, StatusCode> {
+    string_header(headers, name)?
+        .map(|value| value.parse::<u64>().map_err(|e| {
+            println!("Invalid {} header: {}", name, e);
+            StatusCode::BAD_REQUEST
+        }))
+        .transpose()
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_lifetimes_that_overflow_or_exceed_the_maximum() {
+        let settings = ValidationSettings::default();
+        let claims = |expires_in| ClaimOptions { expires_in: Some(expires_in), ..ClaimOptions::default() }
+            .into_claims(Value::Null, &settings);
+
+        assert_eq!(claims(u64::MAX).unwrap_err(), StatusCode::BAD_REQUEST);
+        assert_eq!(claims(settings.max_lifetime + 1).unwrap_err(), StatusCode::BAD_REQUEST);
+        assert!(claims(settings.max_lifetime).is_ok());
+    }
+
+    #[test]
+    fn rejects_unexpected_issuers() {
+        let mut settings = ValidationSettings::default();
+        let anyone = ClaimExpectations::default();
+        let north_pole = ClaimExpectations { iss: Some("north-pole".to_string()), ..ClaimExpectations::default() };
+
+        assert!(anyone.accepts_issuer(None, &settings));
+        assert!(!anyone.accepts_issuer(Some("mallory"), &settings));
+        assert!(north_pole.accepts_issuer(Some("north-pole"), &settings));
+
+        settings.issuers = vec!["north-pole".to_string()];
+        assert!(anyone.accepts_issuer(Some("north-pole"), &settings));
+    }
+}
@@ -0,0 +1,239 @@
+use std::str::FromStr;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{crypto::verify, Algorithm, DecodingKey};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{claims::now, jwe, keyring::{is_hmac, Keyring}};
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryStatus {
+    Valid,
+    Expired,
+    NotYetValid,
+    Missing
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Critical
+}
+
+#[derive(Serialize, Debug)]
+pub struct Risk {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String
+}
+
+impl Risk {
+    fn new(code: &'static str, severity: Severity, message: String) -> Self {
+        Self { code, severity, message }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TokenReport {
+    pub encrypted: bool,
+    pub encryption_header: Option<Value>,
+    pub header: Option<Value>,
+    pub claims: Option<Value>,
+    pub algorithm: Option<String>,
+    pub kid: Option<String>,
+    pub expiry: ExpiryStatus,
+    pub expires_in: Option<i64>,
+    pub lifetime: Option<u64>,
+    pub signature_verified: bool,
+    pub verified_by: Option<String>,
+    pub risks: Vec<Risk>
+}
+
+/// Decodes without trusting anything in the token. Only fails when the token
+/// isn't a JWS or JWE at all.
+pub fn inspect(keyring: &Keyring, token: &str) -> Result<TokenReport, String> {
+    if !jwe::is_jwe(token) {
+        return inspect_jws(keyring, token, None)
+    }
+
+    let encryption_header = decode_segment(token.split('.').next().unwrap_or_default())
+        .map_err(|_| "Invalid JWE header".to_string())?;
+
+    match jwe::decrypt(keyring.encryption_keys(), token) {
+        Ok(inner) => inspect_jws(keyring, &inner, Some(encryption_header)),
+        Err(e) => Ok(TokenReport {
+            encrypted: true,
+            encryption_header: Some(encryption_header),
+            header: None,
+            claims: None,
+            algorithm: None,
+            kid: None,
+            expiry: ExpiryStatus::Missing,
+            expires_in: None,
+            lifetime: None,
+            signature_verified: false,
+            verified_by: None,
+            risks: vec![Risk::new(
+                "undecryptable",
+                Severity::Warning,
+                format!("Token can't be decrypted with any configured key: {:?}", e)
+            )]
+        })
+    }
+}
+
+fn inspect_jws(
+    keyring: &Keyring,
+    token: &str,
+    encryption_header: Option<Value>
+) -> Result<TokenReport, String> {
+    let [encoded_header, encoded_claims, signature] = token.split('.').collect::<Vec<_>>()[..] else {
+        return Err("Token is neither a JWS nor a JWE".to_string())
+    };
+
+    let header = decode_segment(encoded_header).map_err(|_| "Invalid JWT header".to_string())?;
+    let claims = decode_segment(encoded_claims).map_err(|_| "Invalid JWT claims".to_string())?;
+
+    let algorithm_name = header.get("alg").and_then(Value::as_str).map(str::to_string);
+    let kid = header.get("kid").and_then(Value::as_str).map(str::to_string);
+    let algorithm = algorithm_name.as_deref().and_then(|name| Algorithm::from_str(name).ok());
+
+    let mut risks = Vec::new();
+    let message = format!("{}.{}", encoded_header, encoded_claims);
+
+    match (&algorithm_name, algorithm) {
+        (None, _) => risks.push(Risk::new(
+            "missing_alg", Severity::Critical, "Header has no alg".to_string()
+        )),
+        (Some(name), _) if name.eq_ignore_ascii_case("none") => risks.push(Risk::new(
+            "alg_none", Severity::Critical, "Token is unsigned (alg: none)".to_string()
+        )),
+        (Some(name), None) => risks.push(Risk::new(
+            "unknown_alg", Severity::Warning, format!("Unsupported algorithm {}", name)
+        )),
+        _ => ()
+    }
+
+    let verified_by = algorithm.and_then(|algorithm| {
+        keyring.keys().iter()
+            .filter(|key| key.algorithm == algorithm)
+            .find(|key| verify(signature, message.as_bytes(), key.decoding_key(), algorithm).unwrap_or(false))
+            .map(|key| key.kid.clone())
+    });
+
+    if let Some(algorithm) = algorithm.filter(|algorithm| is_hmac(*algorithm)) {
+        if let Some(key) = kid.as_deref().and_then(|kid| keyring.get(kid)) {
+            if !is_hmac(key.algorithm) {
+                risks.push(Risk::new(
+                    "hmac_with_asymmetric_kid",
+                    Severity::Critical,
+                    format!("{:?} token names {:?} key {}", algorithm, key.algorithm, key.kid)
+                ));
+            }
+        }
+
+        // Classic confusion: the public key PEM used as the HMAC secret
+        let confused_with = keyring.keys().iter()
+            .filter_map(|key| key.public_key().map(|public_key| (key, public_key)))
+            .find(|(_, public_key)| {
+                let pem = String::from_utf8_lossy(&public_key.pem);
+                [pem.as_ref(), pem.trim_end()].iter().any(|secret| {
+                    verify(signature, message.as_bytes(), &DecodingKey::from_secret(secret.as_bytes()), algorithm)
+                        .unwrap_or(false)
+                })
+            })
+            .map(|(key, _)| key.kid.clone());
+
+        if let Some(confused_with) = confused_with {
+            risks.push(Risk::new(
+                "hmac_with_public_key",
+                Severity::Critical,
+                format!("Signature is an HMAC keyed with the public key of {}", confused_with)
+            ));
+        }
+    }
+
+    if let (Some(algorithm), Some(key)) = (algorithm, kid.as_deref().and_then(|kid| keyring.get(kid))) {
+        // HMAC against an asymmetric key is already reported as confusion
+        if key.algorithm != algorithm && (!is_hmac(algorithm) || is_hmac(key.algorithm)) {
+            risks.push(Risk::new(
+                "algorithm_mismatch",
+                Severity::Warning,
+                format!("Key {} is {:?} but the token uses {:?}", key.kid, key.algorithm, algorithm)
+            ));
+        }
+    }
+
+    let now = now() as i64;
+    let exp = claims.get("exp").and_then(Value::as_i64);
+    let nbf = claims.get("nbf").and_then(Value::as_i64);
+    let iat = claims.get("iat").and_then(Value::as_i64);
+
+    let expiry = match (exp, nbf) {
+        (_, Some(nbf)) if nbf > now => ExpiryStatus::NotYetValid,
+        (Some(exp), _) if exp <= now => ExpiryStatus::Expired,
+        (Some(_), _) => ExpiryStatus::Valid,
+        (None, _) => ExpiryStatus::Missing
+    };
+
+    if exp.is_none() {
+        risks.push(Risk::new(
+            "no_expiry", Severity::Warning, "Token never expires".to_string()
+        ));
+    }
+
+    // Claims come from the token, so they can be anything
+    let lifetime = exp.map(|exp| exp.saturating_sub(iat.or(nbf).unwrap_or(now)).max(0) as u64);
+    let max_lifetime = keyring.validation.max_lifetime;
+
+    if let Some(lifetime) = lifetime.filter(|lifetime| *lifetime > max_lifetime) {
+        risks.push(Risk::new(
+            "excessive_lifetime",
+            Severity::Warning,
+            format!("Token lives for {} seconds, more than {}", lifetime, max_lifetime)
+        ));
+    }
+
+    Ok(TokenReport {
+        encrypted: encryption_header.is_some(),
+        encryption_header,
+        header: Some(header),
+        claims: Some(claims),
+        algorithm: algorithm_name,
+        kid,
+        expiry,
+        expires_in: exp.map(|exp| exp.saturating_sub(now)),
+        lifetime,
+        signature_verified: verified_by.is_some(),
+        verified_by,
+        risks
+    })
+}
+
+fn decode_segment(segment: &str) -> Result<Value, ()> {
+    let bytes = URL_SAFE_NO_PAD.decode(segment).map_err(|_| ())?;
+    serde_json::from_slice(&bytes).map_err(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn survives_extreme_claims() {
+        let segment = |value: &str| URL_SAFE_NO_PAD.encode(value);
+        let token = format!(
+            "{}.{}.{}",
+            segment(r#"{"alg":"HS256","typ":"JWT"}"#),
+            segment(&format!(r#"{{"exp":{},"iat":{}}}"#, i64::MAX, i64::MIN)),
+            segment("signature")
+        );
+
+        let report = inspect(&Keyring::generated(), &token).unwrap();
+
+        assert_eq!(report.lifetime, Some(i64::MAX as u64));
+        assert!(report.risks.iter().any(|risk| risk.code == "excessive_lifetime"));
+    }
+}
@@ -0,0 +1,289 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+        PublicKeyUse, RSAKeyParameters, RSAKeyType
+    },
+    Algorithm, DecodingKey
+};
+use simple_asn1::{from_der, ASN1Block, BigInt};
+
+const RSA_ENCRYPTION: [u64; 7] = [1, 2, 840, 113549, 1, 1, 1];
+const EC_PUBLIC_KEY: [u64; 6] = [1, 2, 840, 10045, 2, 1];
+const PRIME256V1: [u64; 7] = [1, 2, 840, 10045, 3, 1, 7];
+const SECP384R1: [u64; 5] = [1, 3, 132, 0, 34];
+const ED25519: [u64; 4] = [1, 3, 101, 112];
+
+/// A public key parsed from PEM, with what is needed to verify tokens and to
+/// publish it in a JWKS document.
+pub struct PublicKey {
+    pub decoding_key: DecodingKey,
+    pub algorithms: Vec<Algorithm>,
+    pub pem: Vec<u8>,
+    parameters: AlgorithmParameters
+}
+
+impl PublicKey {
+    /// Accepts SPKI (`PUBLIC KEY`) PEMs for RSA, P-256, P-384 and Ed25519
+    /// keys, as well as PKCS#1 (`RSA PUBLIC KEY`) PEMs.
+    pub fn from_pem(pem_bytes: &[u8]) -> Result<Self, String> {
+        let pem = pem::parse(pem_bytes).map_err(|e| format!("Invalid PEM: {}", e))?;
+
+        match pem.tag() {
+            "RSA PUBLIC KEY" => {
+                let parameters = rsa_parameters(pem.contents())?;
+                Self::build(pem_bytes, parameters)
+            },
+            "PUBLIC KEY" => {
+                let SubjectPublicKeyInfo { algorithm: oid, curve: curve_oid, key } = parse_spki(pem.contents())?;
+
+                let parameters = if oid == RSA_ENCRYPTION {
+                    rsa_parameters(&key)?
+                } else if oid == EC_PUBLIC_KEY {
+                    let (curve, coordinate_len) = match curve_oid.as_deref() {
+                        Some(curve_oid) if curve_oid == PRIME256V1 => (EllipticCurve::P256, 32),
+                        Some(curve_oid) if curve_oid == SECP384R1 => (EllipticCurve::P384, 48),
+                        _ => return Err("Unsupported elliptic curve".to_string())
+                    };
+
+                    // Uncompressed point: 0x04 || x || y
+                    if key.len() != 1 + 2 * coordinate_len || key[0] != 0x04 {
+                        return Err("Only uncompressed EC points are supported".to_string())
+                    }
+
+                    AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                        key_type: EllipticCurveKeyType::EC,
+                        curve,
+                        x: URL_SAFE_NO_PAD.encode(&key[1..1 + coordinate_len]),
+                        y: URL_SAFE_NO_PAD.encode(&key[1 + coordinate_len..])
+                    })
+                } else if oid == ED25519 {
+                    AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                        key_type: OctetKeyPairType::OctetKeyPair,
+                        curve: EllipticCurve::Ed25519,
+                        x: URL_SAFE_NO_PAD.encode(&key)
+                    })
+                } else {
+                    return Err("Unsupported public key algorithm".to_string())
+                };
+
+                Self::build(pem_bytes, parameters)
+            },
+            tag => Err(format!("Expected a PUBLIC KEY PEM, got {}", tag))
+        }
+    }
+
+    fn build(pem_bytes: &[u8], parameters: AlgorithmParameters) -> Result<Self, String> {
+        let (decoding_key, algorithms) = match &parameters {
+            AlgorithmParameters::RSA(_) => (
+                DecodingKey::from_rsa_pem(pem_bytes),
+                vec![
+                    Algorithm::RS256, Algorithm::RS384, Algorithm::RS512,
+                    Algorithm::PS256, Algorithm::PS384, Algorithm::PS512
+                ]
+            ),
+            AlgorithmParameters::EllipticCurve(ec) => (
+                DecodingKey::from_ec_pem(pem_bytes),
+                match ec.curve {
+                    EllipticCurve::P384 => vec![Algorithm::ES384],
+                    _ => vec![Algorithm::ES256]
+                }
+            ),
+            _ => (DecodingKey::from_ed_pem(pem_bytes), vec![Algorithm::EdDSA])
+        };
+
+        Ok(Self {
+            decoding_key: decoding_key.map_err(|e| format!("Invalid public key: {}", e))?,
+            algorithms,
+            pem: pem_bytes.to_vec(),
+            parameters
+        })
+    }
+
+    pub fn to_jwk(&self, kid: &str, algorithm: Algorithm) -> Jwk {
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: key_algorithm(algorithm),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm: self.parameters.clone()
+        }
+    }
+}
+
+fn key_algorithm(algorithm: Algorithm) -> Option<KeyAlgorithm> {
+    match algorithm {
+        Algorithm::RS256 => Some(KeyAlgorithm::RS256),
+        Algorithm::RS384 => Some(KeyAlgorithm::RS384),
+        Algorithm::RS512 => Some(KeyAlgorithm::RS512),
+        Algorithm::PS256 => Some(KeyAlgorithm::PS256),
+        Algorithm::PS384 => Some(KeyAlgorithm::PS384),
+        Algorithm::PS512 => Some(KeyAlgorithm::PS512),
+        Algorithm::ES256 => Some(KeyAlgorithm::ES256),
+        Algorithm::ES384 => Some(KeyAlgorithm::ES384),
+        Algorithm::EdDSA => Some(KeyAlgorithm::EdDSA),
+        _ => None
+    }
+}
+
+/// The parts of a SubjectPublicKeyInfo we care about: the algorithm OID, the
+/// curve OID for EC keys, and the raw subject public key.
+struct SubjectPublicKeyInfo {
+    algorithm: Vec<u64>,
+    curve: Option<Vec<u64>>,
+    key: Vec<u8>
+}
+
+fn parse_spki(der: &[u8]) -> Result<SubjectPublicKeyInfo, String> {
+    let invalid = || "Invalid SubjectPublicKeyInfo".to_string();
+    let blocks = from_der(der).map_err(|_| invalid())?;
+
+    let Some(ASN1Block::Sequence(_, spki)) = blocks.first() else { return Err(invalid()) };
+    let [ASN1Block::Sequence(_, algorithm), ASN1Block::BitString(_, _, key)] = spki.as_slice() else {
+        return Err(invalid())
+    };
+
+    let Some(ASN1Block::ObjectIdentifier(_, oid)) = algorithm.first() else { return Err(invalid()) };
+    let oid = oid.as_vec::<u64>().map_err(|_| invalid())?;
+
+    let curve_oid = match algorithm.get(1) {
+        Some(ASN1Block::ObjectIdentifier(_, curve)) => Some(curve.as_vec::<u64>().map_err(|_| invalid())?),
+        _ => None
+    };
+
+    Ok(SubjectPublicKeyInfo {
+        algorithm: oid,
+        curve: curve_oid,
+        key: key.clone()
+    })
+}
+
+fn rsa_parameters(pkcs1_der: &[u8]) -> Result<AlgorithmParameters, String> {
+    let invalid = || "Invalid RSA public key".to_string();
+    let blocks = from_der(pkcs1_der).map_err(|_| invalid())?;
+
+    let Some(ASN1Block::Sequence(_, integers)) = blocks.first() else { return Err(invalid()) };
+    let [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] = integers.as_slice() else {
+        return Err(invalid())
+    };
+
+    Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+        key_type: RSAKeyType::RSA,
+        n: URL_SAFE_NO_PAD.encode(unsigned_bytes(n)),
+        e: URL_SAFE_NO_PAD.encode(unsigned_bytes(e))
+    }))
+}
+
+fn unsigned_bytes(integer: &BigInt) -> Vec<u8> {
+    let (_, bytes) = integer.to_bytes_be();
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSA_SPKI: &str = "-----BEGIN PUBLIC KEY-----
+MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQCi9pktZTP/lCPUbEVlEAWbD47g
+gqHiOdMK58SrILXb2cQDI3i5tLAihNXxTJeKrrmRFNsTdHL06xfD16CR7YPOUkHo
+FBP8egUABLgxefcPWrTnwFGdmP23oQQif2LS7o9GNhPi7gqyo/EBBOyjDnKNkQQb
+PnWr8tgLMezuCXFL8wIDAQAB
+-----END PUBLIC KEY-----
+";
+
+    // The same key as RSA_SPKI.
+    const RSA_PKCS1: &str = "-----BEGIN RSA PUBLIC KEY-----
+MIGJAoGBAKL2mS1lM/+UI9RsRWUQBZsPjuCCoeI50wrnxKsgtdvZxAMjeLm0sCKE
+1fFMl4quuZEU2xN0cvTrF8PXoJHtg85SQegUE/x6BQAEuDF59w9atOfAUZ2Y/beh
+BCJ/YtLuj0Y2E+LuCrKj8QEE7KMOco2RBBs+davy2Asx7O4JcUvzAgMBAAE=
+-----END RSA PUBLIC KEY-----
+";
+
+    const RSA_MODULUS: &str = "ovaZLWUz_5Qj1GxFZRAFmw-O4IKh4jnTCufEqyC129nEAyN4ubSwIoTV8UyXiq65kRTbE3Ry9OsXw9egke2DzlJB6BQT_HoFAAS4MXn3D1q058BRnZj9t6EEIn9i0u6PRjYT4u4KsqPxAQTsow5yjZEEGz51q_LYCzHs7glxS_M";
+
+    const P256: &str = "-----BEGIN PUBLIC KEY-----
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEWzG2JXQ2iuYTn5rxLDyXptzejXZv
+eiSvXhR1mWq939T3XlYIBJcGxAKgfgBBzaL70kRRM6nxQjOwEJfzLs2rSA==
+-----END PUBLIC KEY-----
+";
+
+    const P384: &str = "-----BEGIN PUBLIC KEY-----
+MHYwEAYHKoZIzj0CAQYFK4EEACIDYgAEPkXwwBzxaMfpx4zq1Q5juP3heAfyHJ3Z
+dWcV0YDwaQW5par2zMVtKYhzosm2pVSpB56xxnMMEl05FJ58wuDvFLJEzoBxCGRy
+kOuswFH5n0OIu2Bzu0UUaUP5dqpp84he
+-----END PUBLIC KEY-----
+";
+
+    // secp256k1, which JWS doesn't use.
+    const SECP256K1: &str = "-----BEGIN PUBLIC KEY-----
+MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAE39Oo/Pr3hHSaG+fzJvsLQKCKHdIe7h6O
+HIPm+cfctojD1BiUe2lBfGQijsKrK2ykXchoe9cKhQW/sdZzAfnesQ==
+-----END PUBLIC KEY-----
+";
+
+    const ED25519_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VwAyEAM65K0bCB0Goi2aV+abUN86rN6UysKihUEau9FrqTbJs=
+-----END PUBLIC KEY-----
+";
+
+    fn parameters(pem: &str) -> AlgorithmParameters {
+        PublicKey::from_pem(pem.as_bytes()).unwrap().parameters
+    }
+
+    #[test]
+    fn parses_rsa_keys_in_both_formats() {
+        for pem in [RSA_SPKI, RSA_PKCS1] {
+            let AlgorithmParameters::RSA(rsa) = parameters(pem) else { panic!("expected an RSA key") };
+            assert_eq!(rsa.n, RSA_MODULUS);
+            assert_eq!(rsa.e, "AQAB");
+        }
+
+        let key = PublicKey::from_pem(RSA_SPKI.as_bytes()).unwrap();
+        assert!(key.algorithms.contains(&Algorithm::RS256));
+        assert!(key.algorithms.contains(&Algorithm::PS512));
+    }
+
+    #[test]
+    fn parses_ec_keys() {
+        let AlgorithmParameters::EllipticCurve(ec) = parameters(P256) else { panic!("expected an EC key") };
+        assert_eq!(ec.curve, EllipticCurve::P256);
+        assert_eq!(ec.x, "WzG2JXQ2iuYTn5rxLDyXptzejXZveiSvXhR1mWq939Q");
+        assert_eq!(ec.y, "915WCASXBsQCoH4AQc2i-9JEUTOp8UIzsBCX8y7Nq0g");
+        assert_eq!(PublicKey::from_pem(P256.as_bytes()).unwrap().algorithms, vec![Algorithm::ES256]);
+
+        let AlgorithmParameters::EllipticCurve(ec) = parameters(P384) else { panic!("expected an EC key") };
+        assert_eq!(ec.curve, EllipticCurve::P384);
+        assert_eq!(PublicKey::from_pem(P384.as_bytes()).unwrap().algorithms, vec![Algorithm::ES384]);
+    }
+
+    #[test]
+    fn parses_ed25519_keys() {
+        let AlgorithmParameters::OctetKeyPair(okp) = parameters(ED25519_KEY) else { panic!("expected an OKP key") };
+        assert_eq!(okp.curve, EllipticCurve::Ed25519);
+        assert_eq!(okp.x, "M65K0bCB0Goi2aV-abUN86rN6UysKihUEau9FrqTbJs");
+    }
+
+    #[test]
+    fn rejects_unsupported_keys() {
+        let error = |pem: &str| PublicKey::from_pem(pem.as_bytes()).err().unwrap();
+
+        assert_eq!(error(SECP256K1), "Unsupported elliptic curve");
+        assert_eq!(
+            error(&RSA_SPKI.replace("PUBLIC KEY", "PRIVATE KEY")),
+            "Expected a PUBLIC KEY PEM, got PRIVATE KEY"
+        );
+        assert!(error("not a pem").starts_with("Invalid PEM"));
+    }
+
+    #[test]
+    fn publishes_the_kid_and_algorithm() {
+        let jwk = PublicKey::from_pem(P256.as_bytes()).unwrap().to_jwk("gift-es", Algorithm::ES256);
+
+        assert_eq!(jwk.common.key_id.as_deref(), Some("gift-es"));
+        assert_eq!(jwk.common.key_algorithm, Some(KeyAlgorithm::ES256));
+        assert_eq!(jwk.common.public_key_use, Some(PublicKeyUse::Signature));
+    }
+}
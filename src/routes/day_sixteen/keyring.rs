@@ -0,0 +1,407 @@
+use std::{fs, io::ErrorKind, path::Path};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, EncodingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use super::{claims::ValidationSettings, jwe::EncryptionKey, jwk::PublicKey};
+
+pub const GIFT_TOKENS_CONFIG_PATH: &str = "gift_tokens.toml";
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct GiftTokensConfig {
+    active_kid: Option<String>,
+    #[serde(default)]
+    keys: Vec<KeyConfig>,
+    #[serde(default)]
+    encryption_keys: Vec<EncryptionKeyConfig>,
+    #[serde(default)]
+    validation: ValidationSettings
+}
+
+/// Keys for encrypted gift tokens. The last one encrypts new tokens, all of
+/// them can decrypt.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct EncryptionKeyConfig {
+    kid: String,
+    secret: String
+}
+
+/// HMAC keys name the entry in Secrets.toml holding the key material in
+/// `secret`, never the key itself. RSA, EC and Ed25519 keys point at PKCS#8
+/// (or PKCS#1 for RSA) private key and SPKI public key PEM files instead.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct KeyConfig {
+    kid: String,
+    #[serde(default = "default_algorithm")]
+    algorithm: Algorithm,
+    secret: Option<String>,
+    private_key: Option<String>,
+    public_key: Option<String>
+}
+
+fn default_algorithm() -> Algorithm {
+    Algorithm::HS256
+}
+
+pub struct GiftKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    public_key: Option<PublicKey>,
+    /// Added through /16/keys rather than gift_tokens.toml, so lost on
+    /// restart.
+    runtime: bool
+}
+
+impl GiftKey {
+    pub fn hmac(kid: String, secret: &[u8]) -> Self {
+        Self {
+            kid,
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            public_key: None,
+            runtime: false
+        }
+    }
+
+    fn from_config(
+        config: KeyConfig,
+        get_secret: &impl Fn(&str) -> Option<String>
+    ) -> Result<Self, String> {
+        if is_hmac(config.algorithm) {
+            let secret_name = config.secret
+                .ok_or(format!("Key {} needs a secret", config.kid))?;
+
+            let secret = get_secret(&secret_name)
+                .filter(|secret| !secret.is_empty())
+                .ok_or(format!("Secret {} for key {} is not set, add it to Secrets.toml", secret_name, config.kid))?;
+
+            return Ok(Self {
+                algorithm: config.algorithm,
+                ..Self::hmac(config.kid, secret.as_bytes())
+            })
+        }
+
+        let (Some(private_key_path), Some(public_key_path)) = (&config.private_key, &config.public_key) else {
+            return Err(format!("Key {} needs private-key and public-key", config.kid))
+        };
+
+        let private_pem = fs::read(private_key_path)
+            .map_err(|e| format!("Failed to read {}: {}", private_key_path, e))?;
+        let public_pem = fs::read(public_key_path)
+            .map_err(|e| format!("Failed to read {}: {}", public_key_path, e))?;
+
+        let encoding_key = match config.algorithm {
+            Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(&private_pem),
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(&private_pem),
+            _ => EncodingKey::from_rsa_pem(&private_pem)
+        }.map_err(|e| format!("Invalid private key for {}: {}", config.kid, e))?;
+
+        let public_key = PublicKey::from_pem(&public_pem)
+            .map_err(|e| format!("Invalid public key for {}: {}", config.kid, e))?;
+
+        if !public_key.algorithms.contains(&config.algorithm) {
+            return Err(format!("Public key for {} can't be used with {:?}", config.kid, config.algorithm))
+        }
+
+        Ok(Self {
+            kid: config.kid,
+            algorithm: config.algorithm,
+            encoding_key,
+            decoding_key: public_key.decoding_key.clone(),
+            public_key: Some(public_key),
+            runtime: false
+        })
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    pub fn public_key(&self) -> Option<&PublicKey> {
+        self.public_key.as_ref()
+    }
+}
+
+pub fn is_hmac(algorithm: Algorithm) -> bool {
+    matches!(algorithm, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)
+}
+
+#[derive(Serialize, Debug)]
+pub struct KeySummary {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub active: bool,
+    pub runtime: bool
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyringError {
+    DuplicateKid,
+    UnknownKid,
+    ActiveKey,
+    EmptySecret,
+    RuntimeKeyActivation
+}
+
+pub struct Keyring {
+    keys: Vec<GiftKey>,
+    active_kid: String,
+    encryption_keys: Vec<EncryptionKey>,
+    pub validation: ValidationSettings
+}
+
+impl Keyring {
+    /// Loads the key layout from the config file and the key material from
+    /// `get_secret`. Without a config file a random key is generated, so
+    /// tokens won't survive a restart.
+    pub fn load(
+        path: impl AsRef<Path>,
+        get_secret: impl Fn(&str) -> Option<String>
+    ) -> Result<Self, String> {
+        let path = path.as_ref();
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                println!("No gift token config at {}, generating a temporary key", path.display());
+                return Ok(Self::generated())
+            },
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e))
+        };
+
+        let config: GiftTokensConfig = toml::from_str(&contents)
+            .map_err(|e| format!("Invalid gift token config in {}: {}", path.display(), e))?;
+
+        if config.keys.is_empty() {
+            return Err(format!("No keys configured in {}", path.display()))
+        }
+
+        if config.validation.default_lifetime > config.validation.max_lifetime {
+            return Err(format!("default-lifetime in {} exceeds max-lifetime", path.display()))
+        }
+
+        let mut keys: Vec<GiftKey> = Vec::new();
+        for key in config.keys {
+            if keys.iter().any(|existing| existing.kid == key.kid) {
+                return Err(format!("Duplicate key id {} in {}", key.kid, path.display()))
+            }
+
+            keys.push(GiftKey::from_config(key, &get_secret)?);
+        }
+
+        let active_kid = match config.active_kid {
+            Some(active_kid) if keys.iter().any(|key| key.kid == active_kid) => active_kid,
+            Some(active_kid) => return Err(format!("Active key {} is not configured", active_kid)),
+            None => keys[keys.len() - 1].kid.clone()
+        };
+
+        let mut encryption_keys: Vec<EncryptionKey> = Vec::new();
+        for key in config.encryption_keys {
+            let secret = get_secret(&key.secret)
+                .filter(|secret| !secret.is_empty())
+                .ok_or(format!("Secret {} for encryption key {} is not set, add it to Secrets.toml", key.secret, key.kid))?;
+
+            encryption_keys.push(EncryptionKey::from_secret(key.kid, secret.as_bytes()));
+        }
+
+        if encryption_keys.is_empty() {
+            println!("No encryption keys in {}, generating a temporary key", path.display());
+            encryption_keys.push(EncryptionKey::generated());
+        }
+
+        Ok(Self { keys, active_kid, encryption_keys, validation: config.validation })
+    }
+
+    pub fn generated() -> Self {
+        let mut secret = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+
+        let kid = format!("generated-{}", rand::thread_rng().next_u32());
+
+        Self {
+            keys: vec![GiftKey::hmac(kid.clone(), &secret)],
+            active_kid: kid,
+            encryption_keys: vec![EncryptionKey::generated()],
+            validation: ValidationSettings::default()
+        }
+    }
+
+    pub fn active(&self) -> &GiftKey {
+        self.get(&self.active_kid).expect("Active key is always in the keyring")
+    }
+
+    pub fn active_encryption_key(&self) -> &EncryptionKey {
+        self.encryption_keys.last().expect("There is always at least one encryption key")
+    }
+
+    pub fn encryption_keys(&self) -> &[EncryptionKey] {
+        &self.encryption_keys
+    }
+
+    pub fn keys(&self) -> &[GiftKey] {
+        &self.keys
+    }
+
+    pub fn get(&self, kid: &str) -> Option<&GiftKey> {
+        self.keys.iter().find(|key| key.kid == kid)
+    }
+
+    pub fn summaries(&self) -> Vec<KeySummary> {
+        self.keys.iter().map(|key| KeySummary {
+            kid: key.kid.clone(),
+            algorithm: key.algorithm,
+            active: key.kid == self.active_kid,
+            runtime: key.runtime
+        }).collect()
+    }
+
+    /// Public keys of every asymmetric key, so other services can verify gift
+    /// tokens. HMAC secrets are never published.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter()
+                .filter_map(|key| key.public_key.as_ref().map(|public_key| public_key.to_jwk(&key.kid, key.algorithm)))
+                .collect()
+        }
+    }
+
+    /// Adds an HMAC key that verifies tokens until restart. It can't become
+    /// the active key: tokens signed with it would stop verifying once it is
+    /// forgotten, so signing keys go in gift_tokens.toml.
+    pub fn add(
+        &mut self,
+        kid: String,
+        secret: String,
+        activate: bool
+    ) -> Result<(), KeyringError> {
+        if activate {
+            return Err(KeyringError::RuntimeKeyActivation)
+        }
+
+        if secret.is_empty() {
+            return Err(KeyringError::EmptySecret)
+        }
+
+        if self.get(&kid).is_some() {
+            return Err(KeyringError::DuplicateKid)
+        }
+
+        self.keys.push(GiftKey {
+            runtime: true,
+            ..GiftKey::hmac(kid, secret.as_bytes())
+        });
+
+        Ok(())
+    }
+
+    /// Retired keys can no longer verify tokens. The active key has to be
+    /// replaced before it can be retired.
+    pub fn retire(&mut self, kid: &str) -> Result<(), KeyringError> {
+        if kid == self.active_kid {
+            return Err(KeyringError::ActiveKey)
+        }
+
+        let index = self.keys.iter()
+            .position(|key| key.kid == kid)
+            .ok_or(KeyringError::UnknownKid)?;
+
+        self.keys.remove(index);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, config: &str) -> Result<Keyring, String> {
+        let path = std::env::temp_dir().join(format!("gift_tokens_{}_{}.toml", name, std::process::id()));
+        fs::write(&path, config).unwrap();
+
+        let keyring = Keyring::load(&path, |name| match name {
+            "OLD_SECRET" => Some("old".to_string()),
+            "NEW_SECRET" => Some("new".to_string()),
+            "EMPTY_SECRET" => Some(String::new()),
+            _ => None
+        });
+
+        fs::remove_file(&path).unwrap();
+        keyring
+    }
+
+    const TWO_KEYS: &str = "
+        active-kid = \"old\"
+
+        [[keys]]
+        kid = \"old\"
+        secret = \"OLD_SECRET\"
+
+        [[keys]]
+        kid = \"new\"
+        secret = \"NEW_SECRET\"
+    ";
+
+    #[test]
+    fn loads_keys_from_config_and_secrets() {
+        let keyring = load("two_keys", TWO_KEYS).unwrap();
+        assert_eq!(keyring.active().kid, "old");
+        assert_eq!(keyring.keys().len(), 2);
+        assert_eq!(keyring.encryption_keys().len(), 1);
+
+        let keyring = load("last_active", &TWO_KEYS.replace("active-kid = \"old\"", "")).unwrap();
+        assert_eq!(keyring.active().kid, "new");
+    }
+
+    #[test]
+    fn rejects_invalid_configs() {
+        let error = |name, config: &str| load(name, config).err().unwrap();
+
+        assert!(error("missing_secret", &TWO_KEYS.replace("NEW_SECRET", "UNSET_SECRET")).contains("UNSET_SECRET"));
+        assert!(error("empty_secret", &TWO_KEYS.replace("NEW_SECRET", "EMPTY_SECRET")).contains("EMPTY_SECRET"));
+        assert!(error("duplicate", &TWO_KEYS.replace("kid = \"new\"", "kid = \"old\"")).contains("Duplicate key id old"));
+        assert_eq!(error("unknown_active", &TWO_KEYS.replace("active-kid = \"old\"", "active-kid = \"gone\"")), "Active key gone is not configured");
+        assert!(error("no_keys", "active-kid = \"old\"").starts_with("No keys configured"));
+        assert!(error("unknown_field", &format!("{}\n[validation]\nleway = 1", TWO_KEYS)).starts_with("Invalid gift token config"));
+    }
+
+    #[test]
+    fn runtime_keys_verify_but_never_sign() {
+        let mut keyring = load("runtime", TWO_KEYS).unwrap();
+
+        assert_eq!(keyring.add("extra".to_string(), "secret".to_string(), true), Err(KeyringError::RuntimeKeyActivation));
+        assert_eq!(keyring.add("extra".to_string(), String::new(), false), Err(KeyringError::EmptySecret));
+        assert_eq!(keyring.add("old".to_string(), "secret".to_string(), false), Err(KeyringError::DuplicateKid));
+        assert!(keyring.get("extra").is_none());
+
+        keyring.add("extra".to_string(), "secret".to_string(), false).unwrap();
+        assert_eq!(keyring.active().kid, "old");
+
+        let summaries = keyring.summaries();
+        let extra = summaries.iter().find(|summary| summary.kid == "extra").unwrap();
+        assert!(extra.runtime && !extra.active);
+        assert!(summaries.iter().filter(|summary| summary.kid != "extra").all(|summary| !summary.runtime));
+    }
+
+    #[test]
+    fn retires_only_inactive_keys() {
+        let mut keyring = load("retire", TWO_KEYS).unwrap();
+
+        assert_eq!(keyring.retire("old"), Err(KeyringError::ActiveKey));
+        assert_eq!(keyring.retire("gone"), Err(KeyringError::UnknownKid));
+
+        keyring.retire("new").unwrap();
+        assert!(keyring.get("new").is_none());
+        assert_eq!(keyring.keys().len(), 1);
+    }
+}
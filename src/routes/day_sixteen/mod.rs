@@ -0,0 +1,455 @@
+use std::sync::Arc;
+use axum::{body::Bytes, extract::{Path, Query, State}, http::{header, HeaderMap, StatusCode}, Json};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use jsonwebtoken::{encode, Header, decode, decode_header, Validation, errors::ErrorKind, jwk::JwkSet};
+use cookie::{time::Duration, Cookie, SameSite};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+pub mod keyring;
+pub mod jwk;
+pub mod claims;
+pub mod revocation_controller;
+pub mod jwe;
+pub mod inspect;
+
+use claims::{now, ClaimExpectations, ClaimOptions, Claims, QUOTES_AUDIENCE};
+use inspect::TokenReport;
+use jwk::PublicKey;
+use keyring::{KeySummary, Keyring, KeyringError};
+
+use crate::{routes::admin::require_admin, AppState};
+
+const GIFT_COOKIE: &str = "gift";
+
+/// Runtime keys only verify tokens, `activate` is refused. See
+/// `Keyring::add`.
+#[derive(Deserialize, Debug)]
+pub struct KeyForCreation {
+    pub kid: String,
+    pub secret: String,
+    #[serde(default)]
+    pub activate: bool
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenMode {
+    #[default]
+    Signed,
+    Encrypted
+}
+
+/// `?mode=encrypted` (or `X-Gift-Mode: encrypted`) wraps the signed token in a
+/// JWE so the gift can't be read by the client.
+#[derive(Deserialize, Debug)]
+pub struct WrapOptions {
+    pub mode: Option<TokenMode>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExternalToken {
+    pub token: String,
+    pub public_key: String
+}
+
+pub async fn wrap(
+    State(state): State<Arc<AppState>>,
+    Query(options): Query<ClaimOptions>,
+    Query(wrap_options): Query<WrapOptions>,
+    request_headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> Result<(StatusCode, HeaderMap), StatusCode> {
+    let mut headers = HeaderMap::new();
+    println!("payload in wrap: {:?}", payload);
+
+    let keyring = state.gift_keyring.lock().await;
+
+    let options = options.with_headers(&request_headers)?;
+
+    if options.aud.as_deref() == Some(QUOTES_AUDIENCE) {
+        println!("Quote tokens can only be issued by /19/tokens");
+        return Err(StatusCode::BAD_REQUEST)
+    }
+
+    let claim = options.into_claims(payload, &keyring.validation)?;
+
+    let token = sign(&keyring, &claim)?;
+
+    let mode = match wrap_options.mode {
+        Some(mode) => mode,
+        None => match request_headers.get("x-gift-mode").map(|value| value.to_str()) {
+            Some(Ok("encrypted")) => TokenMode::Encrypted,
+            Some(Ok("signed")) | None => TokenMode::Signed,
+            Some(_) => return Err(StatusCode::BAD_REQUEST)
+        }
+    };
+
+    let token = match mode {
+        TokenMode::Signed => token,
+        TokenMode::Encrypted => jwe::encrypt(keyring.active_encryption_key(), &token)
+            .map_err(|e| {
+                println!("Error encrypting jwt: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+    };
+
+    let cookie = gift_cookie(token, claim.exp.saturating_sub(now()));
+
+    headers.insert(
+        header::SET_COOKIE, 
+        cookie.to_string().parse().unwrap()
+    );
+
+    Ok((StatusCode::OK, headers))
+}
+
+pub async fn unwrap(
+    State(state): State<Arc<AppState>>,
+    Query(expectations): Query<ClaimExpectations>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, StatusCode> {
+    let expectations = expectations.with_headers(&headers)?;
+
+    let token = gift_cookie_value(&headers)?;
+
+    let claims = decode_gift(&state, &token, Some(&expectations)).await?;
+
+    if is_revoked(&state, &claims.jti).await? {
+        return Err(StatusCode::UNAUTHORIZED)
+    }
+
+    Ok(Json(claims.gift))
+}
+
+/// Clears the gift cookie.
+pub async fn logout() -> (StatusCode, HeaderMap) {
+    let mut headers = HeaderMap::new();
+
+    let mut cookie = gift_cookie(String::new(), 0);
+    cookie.make_removal();
+
+    headers.insert(
+        header::SET_COOKIE,
+        cookie.to_string().parse().unwrap()
+    );
+
+    (StatusCode::OK, headers)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TokenForRevocation {
+    pub token: String
+}
+
+/// Revokes the gift token given in the body, or the one in the `gift`
+/// cookie when there is no body. A body that isn't a valid request is a 400,
+/// rather than falling back to the cookie.
+pub async fn revoke(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes
+) -> Result<Json<Value>, StatusCode> {
+    let token = match body.is_empty() {
+        true => gift_cookie_value(&headers)?,
+        false => {
+            let Json(body) = Json::<TokenForRevocation>::from_bytes(&body).map_err(|e| {
+                println!("Invalid revocation request: {}", e.body_text());
+                StatusCode::BAD_REQUEST
+            })?;
+
+            body.token
+        }
+    };
+
+    let claims = decode_gift(&state, &token, None).await?;
+
+    // Keep the entry around for as long as unwrap could still accept the token
+    let leeway = state.gift_keyring.lock().await.validation.leeway;
+    let expires_at = DateTime::<Utc>::from_timestamp((claims.exp + leeway) as i64, 0)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let newly_revoked = state.revocation_controller
+        .revoke(&claims.jti, &expires_at)
+        .await
+        .map_err(|e| {
+            println!("Error revoking token: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(json!({
+        "jti": claims.jti,
+        "expires_at": expires_at,
+        "already_revoked": !newly_revoked
+    })))
+}
+
+/// Signs claims with the active key, naming it in the header.
+pub fn sign(keyring: &Keyring, claims: &Claims) -> Result<String, StatusCode> {
+    let key = keyring.active();
+
+    let jwt_header = Header {
+        kid: Some(key.kid.clone()),
+        ..Header::new(key.algorithm)
+    };
+
+    encode(
+        &jwt_header,
+        claims,
+        key.encoding_key()
+    ).map_err(|e| {
+        println!("Error creating jwt: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Checks a bearer token issued for `audience`: signature, expiry, audience
+/// and revocation. Any failure is a 401.
+pub async fn verify_bearer(
+    state: &AppState,
+    token: &str,
+    audience: &str
+) -> Result<Claims, StatusCode> {
+    let expectations = ClaimExpectations {
+        aud: Some(audience.to_string()),
+        ..ClaimExpectations::default()
+    };
+
+    let claims = decode_gift(state, token, Some(&expectations))
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    // The audience is only validated when the token has one
+    if claims.aud.as_deref() != Some(audience) {
+        println!("Bearer token {} isn't meant for {}", claims.jti, audience);
+        return Err(StatusCode::UNAUTHORIZED)
+    }
+
+    if is_revoked(state, &claims.jti).await? {
+        println!("Bearer token {} was revoked", claims.jti);
+        return Err(StatusCode::UNAUTHORIZED)
+    }
+
+    Ok(claims)
+}
+
+fn gift_cookie(token: String, max_age: u64) -> Cookie<'static> {
+    Cookie::build((GIFT_COOKIE, token))
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Strict)
+        .path("/16")
+        .max_age(Duration::seconds(max_age as i64))
+        .build()
+}
+
+/// Finds the gift cookie among every cookie sent, across all Cookie headers.
+fn gift_cookie_value(headers: &HeaderMap) -> Result<String, StatusCode> {
+    headers.get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(Cookie::split_parse)
+        .filter_map(|cookie| cookie.ok())
+        .find(|cookie| cookie.name() == GIFT_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .ok_or_else(|| {
+            println!("No gift cookie in request");
+            StatusCode::BAD_REQUEST
+        })
+}
+
+/// Verifies a gift token against the keyring, decrypting it first if it is
+/// an encrypted token. Without expectations only the signature, expiry and
+/// not-before are checked.
+async fn decode_gift(
+    state: &AppState,
+    token: &str,
+    expectations: Option<&ClaimExpectations>
+) -> Result<Claims, StatusCode> {
+    let keyring = state.gift_keyring.lock().await;
+
+    let decrypted;
+    let token = if jwe::is_jwe(token) {
+        decrypted = jwe::decrypt(keyring.encryption_keys(), token)
+            .map_err(|e| {
+                println!("Failed to decrypt token: {:?}", e);
+                StatusCode::BAD_REQUEST
+            })?;
+        decrypted.as_str()
+    } else {
+        token
+    };
+
+    let jwt_header = decode_header(token)
+        .map_err(|e| {
+            println!("Failed to decode token header: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    // Tokens issued before key rotation carry no kid, try the active key for those
+    let key = match &jwt_header.kid {
+        Some(kid) => keyring.get(kid).ok_or_else(|| {
+            println!("Unknown or retired key id: {}", kid);
+            StatusCode::BAD_REQUEST
+        })?,
+        None => keyring.active()
+    };
+
+    let validation = match expectations {
+        Some(expectations) => expectations.validation(key.algorithm, &keyring.validation),
+        None => ClaimExpectations::any_audience(key.algorithm, &keyring.validation)
+    };
+
+    let decoded = decode::<Claims>(
+        token,
+        key.decoding_key(),
+        &validation
+    ).map_err(|e| {
+        println!("Failed to decode token: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    if expectations.is_some_and(|expectations| !expectations.accepts_issuer(decoded.claims.iss.as_deref(), &keyring.validation)) {
+        println!("Failed to decode token: unexpected issuer");
+        return Err(StatusCode::BAD_REQUEST)
+    }
+
+    Ok(decoded.claims)
+}
+
+async fn is_revoked(state: &AppState, jti: &Uuid) -> Result<bool, StatusCode> {
+    state.revocation_controller
+        .is_revoked(jti)
+        .await
+        .map_err(|e| {
+            println!("Error checking token revocation: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn jwks(
+    State(state): State<Arc<AppState>>,
+) -> Json<JwkSet> {
+    Json(state.gift_keyring.lock().await.jwks())
+}
+
+/// Verifies a JWT signed by someone else against the supplied public key.
+/// Tokens that can't be parsed are a 400, tokens that parse but don't verify
+/// (bad signature, algorithm not matching the key, expired) are a 401.
+pub async fn decode_external(
+    Json(body): Json<ExternalToken>
+) -> Result<Json<Value>, StatusCode> {
+    let public_key = PublicKey::from_pem(body.public_key.as_bytes())
+        .map_err(|e| {
+            println!("Invalid public key: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    let jwt_header = decode_header(&body.token)
+        .map_err(|e| {
+            println!("Failed to decode token header: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    if !public_key.algorithms.contains(&jwt_header.alg) {
+        println!("Token algorithm {:?} doesn't match the public key", jwt_header.alg);
+        return Err(StatusCode::UNAUTHORIZED)
+    }
+
+    let mut validation = Validation::new(jwt_header.alg);
+    validation.required_spec_claims.clear();
+    validation.validate_aud = false;
+
+    let decoded = decode::<Value>(
+        &body.token,
+        &public_key.decoding_key,
+        &validation
+    ).map_err(|e| {
+        println!("Failed to decode token: {}", e);
+        match e.kind() {
+            ErrorKind::InvalidToken
+            | ErrorKind::Base64(_)
+            | ErrorKind::Json(_)
+            | ErrorKind::Utf8(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::UNAUTHORIZED
+        }
+    })?;
+
+    Ok(Json(json!({
+        "header": decoded.header,
+        "claims": decoded.claims
+    })))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TokenForInspection {
+    pub token: String
+}
+
+/// Debugging aid: reports what a token claims to be and flags risky tokens,
+/// without trusting it. Admin only, since it decrypts encrypted gifts.
+pub async fn inspect_token(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<TokenForInspection>
+) -> Result<Json<TokenReport>, StatusCode> {
+    require_admin(&headers, state.admin_token.as_deref())?;
+
+    let keyring = state.gift_keyring.lock().await;
+
+    let report = inspect::inspect(&keyring, body.token.trim())
+        .map_err(|e| {
+            println!("Failed to inspect token: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    Ok(Json(report))
+}
+
+pub async fn list_keys(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<KeySummary>>, StatusCode> {
+    require_admin(&headers, state.admin_token.as_deref())?;
+
+    Ok(Json(state.gift_keyring.lock().await.summaries()))
+}
+
+pub async fn add_key(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<KeyForCreation>
+) -> Result<(StatusCode, Json<Vec<KeySummary>>), StatusCode> {
+    require_admin(&headers, state.admin_token.as_deref())?;
+
+    let mut keyring = state.gift_keyring.lock().await;
+
+    keyring.add(body.kid, body.secret, body.activate)
+        .map_err(keyring_error_status)?;
+
+    Ok((StatusCode::CREATED, Json(keyring.summaries())))
+}
+
+pub async fn retire_key(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(kid): Path<String>
+) -> Result<Json<Vec<KeySummary>>, StatusCode> {
+    require_admin(&headers, state.admin_token.as_deref())?;
+
+    let mut keyring = state.gift_keyring.lock().await;
+
+    keyring.retire(&kid)
+        .map_err(keyring_error_status)?;
+
+    Ok(Json(keyring.summaries()))
+}
+
+fn keyring_error_status(error: KeyringError) -> StatusCode {
+    match error {
+        KeyringError::DuplicateKid | KeyringError::ActiveKey => StatusCode::CONFLICT,
+        KeyringError::UnknownKid => StatusCode::NOT_FOUND,
+        KeyringError::EmptySecret | KeyringError::RuntimeKeyActivation => StatusCode::BAD_REQUEST
+    }
+}
@@ -0,0 +1,127 @@
+use std::{collections::{hash_map::Entry, HashMap}, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use sqlx::{Error, PgPool, SqlitePool};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// The statements below are the same for both databases, only the pool
+/// running them differs. `Memory` goes along with the memory quote store: it
+/// maps revoked token ids to their expiry and forgets them on restart.
+#[derive(Clone)]
+pub enum RevocationStore {
+    Postgres(PgPool),
+    Sqlite(SqlitePool),
+    Memory(Arc<RwLock<HashMap<Uuid, DateTime<Utc>>>>)
+}
+
+#[derive(Clone)]
+pub struct RevocationController {
+    pub store: RevocationStore
+}
+
+impl RevocationController {
+    pub fn build(pool: PgPool) -> Self {
+        Self {
+            store: RevocationStore::Postgres(pool)
+        }
+    }
+
+    pub fn sqlite(pool: SqlitePool) -> Self {
+        Self {
+            store: RevocationStore::Sqlite(pool)
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            store: RevocationStore::Memory(Arc::default())
+        }
+    }
+
+    /// Returns false when the token was already revoked.
+    pub async fn revoke(
+        &self,
+        jti: &Uuid,
+        expires_at: &DateTime<Utc>
+    ) -> Result<bool, Error> {
+        let sql = "INSERT INTO revoked_gift_tokens (jti, expires_at)
+            VALUES ($1, $2)
+            ON CONFLICT (jti) DO NOTHING;";
+
+        let rows_affected = match &self.store {
+            RevocationStore::Postgres(pool) => sqlx::query(sql)
+                .bind(jti)
+                .bind(expires_at)
+                .execute(pool)
+                .await?
+                .rows_affected(),
+            RevocationStore::Sqlite(pool) => sqlx::query(sql)
+                .bind(jti)
+                .bind(expires_at)
+                .execute(pool)
+                .await?
+                .rows_affected(),
+            RevocationStore::Memory(revoked) => match revoked.write().await.entry(*jti) {
+                Entry::Occupied(_) => 0,
+                Entry::Vacant(entry) => {
+                    entry.insert(*expires_at);
+                    1
+                }
+            }
+        };
+
+        Ok(rows_affected > 0)
+    }
+
+    pub async fn is_revoked(
+        &self,
+        jti: &Uuid
+    ) -> Result<bool, Error> {
+        let sql = "SELECT EXISTS (SELECT 1 FROM revoked_gift_tokens WHERE jti = $1);";
+
+        let revoked = match &self.store {
+            RevocationStore::Postgres(pool) => sqlx::query_scalar::<_, bool>(sql)
+                .bind(jti)
+                .fetch_one(pool)
+                .await?,
+            RevocationStore::Sqlite(pool) => sqlx::query_scalar::<_, bool>(sql)
+                .bind(jti)
+                .fetch_one(pool)
+                .await?,
+            RevocationStore::Memory(revoked) => revoked.read().await.contains_key(jti)
+        };
+
+        Ok(revoked)
+    }
+
+    /// Tokens past their expiry are rejected by `unwrap` anyway, so there is
+    /// no need to remember that they were revoked.
+    pub async fn prune_expired(&self) -> Result<u64, Error> {
+        let sql = "DELETE FROM revoked_gift_tokens WHERE expires_at < $1;";
+
+        let rows_affected = match &self.store {
+            RevocationStore::Postgres(pool) => sqlx::query(sql)
+                .bind(Utc::now())
+                .execute(pool)
+                .await?
+                .rows_affected(),
+            RevocationStore::Sqlite(pool) => sqlx::query(sql)
+                .bind(Utc::now())
+                .execute(pool)
+                .await?
+                .rows_affected(),
+            RevocationStore::Memory(revoked) => {
+                let mut revoked = revoked.write().await;
+                let count = revoked.len();
+                let now = Utc::now();
+
+                revoked.retain(|_, expires_at| *expires_at >= now);
+
+                (count - revoked.len()) as u64
+            }
+        };
+
+        Ok(rows_affected)
+    }
+}
@@ -1,3 +1,4 @@
+pub mod admin;
 pub mod day_minus_one;
 pub mod day_two;
 pub mod day_five;
@@ -5,4 +6,5 @@ pub mod day_nine;
 pub mod day_twelve;
 pub mod day_sixteen;
 pub mod day_nineteen;
-pub mod day_twenty_three;
\ No newline at end of file
+pub mod day_twenty_three;
+pub mod schema;
\ No newline at end of file
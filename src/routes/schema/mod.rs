@@ -0,0 +1,364 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json
+};
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::MigrateError;
+
+use crate::{routes::admin::require_admin, AppState};
+
+pub mod schema_controller;
+
+use schema_controller::SchemaController;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationState {
+    Pending,
+    Applied,
+    /// Applied, but its up script was changed since.
+    Modified,
+    /// Applied, but unknown to this build.
+    Missing,
+    /// Failed partway.
+    Dirty
+}
+
+/// `description` is `None` for migrations missing from this build.
+#[derive(Serialize, Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub state: MigrationState,
+    pub reversible: bool
+}
+
+/// `current` is the latest applied migration, `None` on an empty database.
+/// The schema is consistent when every migration is applied or pending.
+#[derive(Serialize, Debug)]
+pub struct SchemaReport {
+    pub database: &'static str,
+    pub current: Option<i64>,
+    pub consistent: bool,
+    pub migrations: Vec<MigrationStatus>
+}
+
+/// `target` is the version to roll back to, `0` reverts every migration.
+#[derive(Deserialize, Debug)]
+pub struct RollbackRequest {
+    pub target: i64
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RollbackQuery {
+    #[serde(default)]
+    pub dry_run: bool
+}
+
+/// `reverted` is newest first. With `dry_run`, it lists what would be
+/// reverted and the schema is left alone.
+#[derive(Serialize, Debug)]
+pub struct RollbackReport {
+    pub dry_run: bool,
+    pub reverted: Vec<i64>,
+    pub schema: SchemaReport
+}
+
+/// What went wrong with the migrations and how to get out of it, for the
+/// startup failure and the logs.
+pub fn explain(error: &MigrateError) -> String {
+    match error {
+        MigrateError::VersionMissing(version) => format!(
+            "Migration {} is applied to the database but isn't part of this build. \
+            Deploy a build that has it, or roll it back with /admin/migrations/rollback first",
+            version
+        ),
+        MigrateError::VersionMismatch(version) => format!(
+            "Migration {} was changed after it was applied. Restore its up script and \
+            make further changes in a new migration",
+            version
+        ),
+        MigrateError::VersionTooNew(version, target) => format!(
+            "Migrations were rolled back to {} and this build would apply {} again. \
+            Deploy the build that expects the older schema, or delete the row from \
+            schema_hold to apply them",
+            target, version
+        ),
+        MigrateError::Dirty(version) => format!(
+            "Migration {} failed partway. Repair the schema by hand, then delete its row \
+            from _sqlx_migrations",
+            version
+        ),
+        MigrateError::ExecuteMigration(e, version) => format!("Migration {} failed: {}", version, e),
+        e => format!("Migrations failed: {}", e)
+    }
+}
+
+/// The migrations a rollback to `target` reverts, newest first. Refused
+/// while the schema is inconsistent, for targets that aren't applied, and
+/// when a migration to revert has no down script.
+fn reverted_migrations(schema: &SchemaReport, target: i64) -> Result<Vec<i64>, StatusCode> {
+    if !schema.consistent {
+        println!("Refusing to roll back migrations while the schema is inconsistent");
+        return Err(StatusCode::CONFLICT)
+    }
+
+    let target_known = schema.migrations.iter().any(|migration| migration.version == target);
+
+    if target != 0 && !target_known {
+        println!("Can't roll back to migration {}, the {} database has no such migration", target, schema.database);
+        return Err(StatusCode::BAD_REQUEST)
+    }
+
+    let target_applied = schema.migrations.iter().any(|migration| {
+        migration.version == target && migration.state == MigrationState::Applied
+    });
+
+    if target != 0 && !target_applied {
+        println!("Can't roll back to migration {}, it isn't applied", target);
+        return Err(StatusCode::BAD_REQUEST)
+    }
+
+    let reverted = schema.migrations.iter()
+        .rev()
+        .filter(|migration| migration.state == MigrationState::Applied && migration.version > target)
+        .collect::<Vec<_>>();
+
+    // sqlx silently skips migrations without a down script
+    if let Some(migration) = reverted.iter().find(|migration| !migration.reversible) {
+        println!("Can't roll back migration {}, it has no down script", migration.version);
+        return Err(StatusCode::CONFLICT)
+    }
+
+    Ok(reverted.iter().map(|migration| migration.version).collect())
+}
+
+/// The controller of the database in use, 501 when everything is kept in
+/// memory.
+fn schema_controller(state: &AppState) -> Result<&SchemaController, StatusCode> {
+    state.schema_controller.as_ref().ok_or_else(|| {
+        println!("There are no migrations, nothing is kept in a database");
+        StatusCode::NOT_IMPLEMENTED
+    })
+}
+
+/// Refuses requests with a 503 once migrations were rolled back under the
+/// running service, as the database no longer has what this build expects.
+pub async fn require_current_schema(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next
+) -> Response {
+    if state.schema_controller.as_ref().is_some_and(SchemaController::rolled_back) {
+        println!("Refusing {}, migrations were rolled back", request.uri().path());
+        return StatusCode::SERVICE_UNAVAILABLE.into_response()
+    }
+
+    next.run(request).await
+}
+
+/// Admin only.
+pub async fn list_migrations(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap
+) -> Result<Json<SchemaReport>, StatusCode> {
+    require_admin(&headers, state.admin_token.as_deref())?;
+
+    match schema_controller(&state)?
+        .report()
+        .await
+    {
+        Ok(report) => Ok(Json(report)),
+        Err(e) => {
+            println!("Error listing migrations: {}", explain(&e));
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Reverts every applied migration newer than `target`. Admin only, and
+/// refused while the schema is inconsistent. Reverting drops tables and
+/// columns along with their data, so the routes kept in the database answer
+/// 503 from then on, and this build refuses to start rather than apply the
+/// reverted migrations again. Deploy the build that expects the older schema
+/// next.
+pub async fn rollback_migrations(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RollbackQuery>,
+    headers: HeaderMap,
+    Json(body): Json<RollbackRequest>
+) -> Result<Json<RollbackReport>, StatusCode> {
+    require_admin(&headers, state.admin_token.as_deref())?;
+
+    let controller = schema_controller(&state)?;
+
+    let schema = controller.report().await.map_err(|e| {
+        println!("Error listing migrations: {}", explain(&e));
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let reverted = reverted_migrations(&schema, body.target)?;
+
+    if query.dry_run || reverted.is_empty() {
+        return Ok(Json(RollbackReport {
+            dry_run: query.dry_run,
+            reverted,
+            schema
+        }))
+    }
+
+    controller.rollback(body.target).await.map_err(|e| {
+        println!("Error rolling back migrations: {}", explain(&e));
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    println!("Rolled back migrations {:?}", reverted);
+
+    let schema = controller.report().await.map_err(|e| {
+        println!("Error listing migrations: {}", explain(&e));
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(RollbackReport {
+        dry_run: false,
+        reverted,
+        schema
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+
+    use super::*;
+    use schema_controller::SchemaStore;
+
+    const LATEST: i64 = 20250112120000;
+
+    /// Every connection to `sqlite::memory:` opens a database of its own.
+    async fn controller() -> SchemaController {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        SchemaController::sqlite(pool)
+    }
+
+    fn pool(controller: &SchemaController) -> &SqlitePool {
+        match &controller.store {
+            SchemaStore::Sqlite(pool) => pool,
+            SchemaStore::Postgres(_) => unreachable!()
+        }
+    }
+
+    fn states(report: &SchemaReport) -> Vec<(i64, MigrationState)> {
+        report.migrations.iter().map(|migration| (migration.version, migration.state)).collect()
+    }
+
+    #[tokio::test]
+    async fn reports_pending_and_applied_migrations() {
+        let controller = controller().await;
+
+        let report = controller.report().await.unwrap();
+        assert_eq!((report.database, report.current, report.consistent), ("sqlite", None, true));
+        assert!(report.migrations.iter().all(|migration| migration.state == MigrationState::Pending && migration.reversible));
+
+        controller.migrate().await.unwrap();
+
+        let report = controller.report().await.unwrap();
+        assert_eq!((report.current, report.consistent), (Some(LATEST), true));
+        assert!(report.migrations.iter().all(|migration| migration.state == MigrationState::Applied));
+    }
+
+    #[tokio::test]
+    async fn reports_modified_and_missing_migrations() {
+        let controller = controller().await;
+        controller.migrate().await.unwrap();
+
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = $1;")
+            .bind(LATEST)
+            .execute(pool(&controller))
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES (30000101000000, 'gone', TRUE, x'00', 0);")
+            .execute(pool(&controller))
+            .await
+            .unwrap();
+
+        let report = controller.report().await.unwrap();
+        let states = states(&report);
+
+        assert!(!report.consistent);
+        assert_eq!(states[states.len() - 2..], [(LATEST, MigrationState::Modified), (30000101000000, MigrationState::Missing)]);
+        assert_eq!(reverted_migrations(&report, 0), Err(StatusCode::CONFLICT));
+    }
+
+    #[tokio::test]
+    async fn validates_rollback_targets() {
+        let controller = controller().await;
+        controller.migrate().await.unwrap();
+
+        let report = controller.report().await.unwrap();
+
+        assert_eq!(reverted_migrations(&report, LATEST), Ok(Vec::new()));
+        assert_eq!(reverted_migrations(&report, 20250109120000), Ok(vec![LATEST, 20250111120000]));
+        assert_eq!(reverted_migrations(&report, 0).unwrap().len(), report.migrations.len());
+        // Postgres only
+        assert_eq!(reverted_migrations(&report, 20250110120000), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(reverted_migrations(&report, 1), Err(StatusCode::BAD_REQUEST));
+
+        controller.rollback(20250109120000).await.unwrap();
+
+        let report = controller.report().await.unwrap();
+        assert_eq!(report.current, Some(20250109120000));
+        assert_eq!(reverted_migrations(&report, 20250111120000), Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn refuses_to_revert_irreversible_migrations() {
+        let controller = controller().await;
+        controller.migrate().await.unwrap();
+
+        let mut report = controller.report().await.unwrap();
+        let irreversible = report.migrations.iter_mut()
+            .find(|migration| migration.version == 20250111120000)
+            .unwrap();
+        irreversible.reversible = false;
+
+        assert_eq!(reverted_migrations(&report, 20250109120000), Err(StatusCode::CONFLICT));
+        assert_eq!(reverted_migrations(&report, 20250111120000), Ok(vec![LATEST]));
+    }
+
+    #[tokio::test]
+    async fn holds_rolled_back_migrations() {
+        let controller = controller().await;
+        controller.migrate().await.unwrap();
+        assert!(!controller.rolled_back());
+
+        controller.rollback(20250109120000).await.unwrap();
+        assert!(controller.rolled_back());
+
+        let report = controller.report().await.unwrap();
+        assert_eq!(states(&report)[report.migrations.len() - 2..], [
+            (20250111120000, MigrationState::Pending),
+            (LATEST, MigrationState::Pending)
+        ]);
+
+        let Err(MigrateError::VersionTooNew(20250111120000, 20250109120000)) = controller.migrate().await else {
+            panic!("rolled back migrations were applied again")
+        };
+
+        sqlx::query("DELETE FROM schema_hold;").execute(pool(&controller)).await.unwrap();
+        controller.migrate().await.unwrap();
+
+        assert_eq!(controller.report().await.unwrap().current, Some(LATEST));
+    }
+}
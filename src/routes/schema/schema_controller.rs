@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    sync::{atomic::{AtomicBool, Ordering}, Arc}
+};
+
+use sqlx::{
+    migrate::{AppliedMigration, Migrate, MigrateError, Migrator},
+    PgPool, SqlitePool
+};
+
+use super::{MigrationState, MigrationStatus, SchemaReport};
+
+/// Migrations of the Postgres database.
+pub static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Migrations of the SQLite database. `migrate!()` skips subdirectories, so
+/// these never run against Postgres. Each mirrors the Postgres migration of
+/// the same version; the Postgres-only ones have no counterpart.
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// The database whose schema is managed: the one quotes or gift token
+/// revocations are kept in.
+#[derive(Clone)]
+pub enum SchemaStore {
+    Postgres(PgPool),
+    Sqlite(SqlitePool)
+}
+
+/// Table holding the version migrations were last rolled back to. Created
+/// on demand rather than by a migration, so rolling back every migration
+/// keeps it.
+const SCHEMA_HOLD_TABLE: &str = "CREATE TABLE IF NOT EXISTS schema_hold (target BIGINT NOT NULL);";
+
+#[derive(Clone)]
+pub struct SchemaController {
+    pub store: SchemaStore,
+    rolled_back: Arc<AtomicBool>
+}
+
+impl SchemaController {
+    pub fn build(pool: PgPool) -> Self {
+        Self {
+            store: SchemaStore::Postgres(pool),
+            rolled_back: Arc::default()
+        }
+    }
+
+    pub fn sqlite(pool: SqlitePool) -> Self {
+        Self {
+            store: SchemaStore::Sqlite(pool),
+            rolled_back: Arc::default()
+        }
+    }
+
+    /// Whether migrations were rolled back since the service started, leaving
+    /// the database behind what this build expects.
+    pub fn rolled_back(&self) -> bool {
+        self.rolled_back.load(Ordering::Relaxed)
+    }
+
+    fn migrator(&self) -> &'static Migrator {
+        match self.store {
+            SchemaStore::Postgres(_) => &POSTGRES_MIGRATOR,
+            SchemaStore::Sqlite(_) => &SQLITE_MIGRATOR
+        }
+    }
+
+    /// Applies pending migrations, after checking the applied ones are still
+    /// the same. Migrations newer than a rollback aren't applied again: that
+    /// fails with `VersionTooNew` until the hold is lifted.
+    pub async fn migrate(&self) -> Result<(), MigrateError> {
+        if let Some(target) = self.hold().await? {
+            let newer = self.migrator().iter()
+                .find(|migration| !migration.migration_type.is_down_migration() && migration.version > target);
+
+            if let Some(migration) = newer {
+                return Err(MigrateError::VersionTooNew(migration.version, target))
+            }
+        }
+
+        match &self.store {
+            SchemaStore::Postgres(pool) => POSTGRES_MIGRATOR.run(pool).await,
+            SchemaStore::Sqlite(pool) => SQLITE_MIGRATOR.run(pool).await
+        }
+    }
+
+    /// Reverts the applied migrations newer than `target`, newest first, and
+    /// holds the schema there.
+    pub async fn rollback(&self, target: i64) -> Result<(), MigrateError> {
+        match &self.store {
+            SchemaStore::Postgres(pool) => {
+                sqlx::raw_sql(SCHEMA_HOLD_TABLE).execute(pool).await?;
+                let mut tx = pool.begin().await?;
+                sqlx::query("DELETE FROM schema_hold;").execute(&mut *tx).await?;
+                sqlx::query("INSERT INTO schema_hold (target) VALUES ($1);").bind(target).execute(&mut *tx).await?;
+                tx.commit().await?;
+            },
+            SchemaStore::Sqlite(pool) => {
+                sqlx::raw_sql(SCHEMA_HOLD_TABLE).execute(pool).await?;
+                let mut tx = pool.begin().await?;
+                sqlx::query("DELETE FROM schema_hold;").execute(&mut *tx).await?;
+                sqlx::query("INSERT INTO schema_hold (target) VALUES ($1);").bind(target).execute(&mut *tx).await?;
+                tx.commit().await?;
+            }
+        }
+
+        self.rolled_back.store(true, Ordering::Relaxed);
+
+        match &self.store {
+            SchemaStore::Postgres(pool) => POSTGRES_MIGRATOR.undo(pool, target).await,
+            SchemaStore::Sqlite(pool) => SQLITE_MIGRATOR.undo(pool, target).await
+        }
+    }
+
+    /// The version migrations were rolled back to, unless the hold was lifted
+    /// by deleting it.
+    async fn hold(&self) -> Result<Option<i64>, MigrateError> {
+        let sql = "SELECT max(target) FROM schema_hold;";
+
+        let target = match &self.store {
+            SchemaStore::Postgres(pool) => {
+                sqlx::raw_sql(SCHEMA_HOLD_TABLE).execute(pool).await?;
+                sqlx::query_scalar::<_, Option<i64>>(sql).fetch_one(pool).await?
+            },
+            SchemaStore::Sqlite(pool) => {
+                sqlx::raw_sql(SCHEMA_HOLD_TABLE).execute(pool).await?;
+                sqlx::query_scalar::<_, Option<i64>>(sql).fetch_one(pool).await?
+            }
+        };
+
+        Ok(target)
+    }
+
+    /// Every migration this build knows of, along with the ones the database
+    /// has applied that it doesn't.
+    pub async fn report(&self) -> Result<SchemaReport, MigrateError> {
+        let (database, (applied, dirty)) = match &self.store {
+            SchemaStore::Postgres(pool) => ("postgres", applied_migrations(&mut *pool.acquire().await?).await?),
+            SchemaStore::Sqlite(pool) => ("sqlite", applied_migrations(&mut *pool.acquire().await?).await?)
+        };
+
+        let migrator = self.migrator();
+        let current = applied.iter().map(|migration| migration.version).max();
+
+        let applied = applied.into_iter()
+            .map(|migration| (migration.version, migration))
+            .collect::<HashMap<_, _>>();
+
+        let mut migrations = migrator.iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| MigrationStatus {
+                version: migration.version,
+                description: Some(migration.description.to_string()),
+                state: match applied.get(&migration.version) {
+                    _ if dirty == Some(migration.version) => MigrationState::Dirty,
+                    Some(applied) if applied.checksum != migration.checksum => MigrationState::Modified,
+                    Some(_) => MigrationState::Applied,
+                    None => MigrationState::Pending
+                },
+                reversible: migrator.iter().any(|down| {
+                    down.version == migration.version && down.migration_type.is_down_migration()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        migrations.extend(applied.keys()
+            .filter(|version| !migrator.version_exists(**version))
+            .map(|version| MigrationStatus {
+                version: *version,
+                description: None,
+                state: if dirty == Some(*version) { MigrationState::Dirty } else { MigrationState::Missing },
+                reversible: false
+            }));
+
+        migrations.sort_by_key(|migration| migration.version);
+
+        Ok(SchemaReport {
+            database,
+            current,
+            consistent: migrations.iter().all(|migration| {
+                matches!(migration.state, MigrationState::Applied | MigrationState::Pending)
+            }),
+            migrations
+        })
+    }
+}
+
+/// The migrations recorded in `_sqlx_migrations`, and the version of one that
+/// failed partway, if any.
+async fn applied_migrations(
+    conn: &mut impl Migrate
+) -> Result<(Vec<AppliedMigration>, Option<i64>), MigrateError> {
+    conn.ensure_migrations_table().await?;
+
+    let dirty = conn.dirty_version().await?;
+
+    Ok((conn.list_applied_migrations().await?, dirty))
+}